@@ -1,34 +1,67 @@
 use crate::data_types::{Object, Return};
 use crate::token::Token;
 use anyhow::Result;
+use strum_macros::IntoStaticStr;
 
 pub trait ExpressionVisitor<T> {
-    fn visit_assignment(&mut self, t: &Token, e: &Expr) -> Result<T>;
+    fn visit_assignment(&mut self, t: &Token, e: &Expr, s: Option<Slot>) -> Result<T>;
     fn visit_binary(&mut self, l: &Expr, o: &Token, r: &Expr) -> Result<T>;
-    fn visit_call(&mut self, c: &Expr, p: &Token, a: &Vec<Box<Expr>>) -> Result<T>;
+    fn visit_call(&mut self, c: &Expr, p: &Token, a: &[Box<Expr>]) -> Result<T>;
+    fn visit_get(&mut self, o: &Expr, n: &Token) -> Result<T>;
     fn visit_grouping(&mut self, g: &Expr) -> Result<T>;
+    fn visit_lambda(&mut self, t: &Token, p: &[Token], b: &[Box<Stmt>]) -> Result<T>;
     fn visit_unary(&mut self, o: &Token, r: &Expr) -> Result<T>;
     fn visit_literal(&mut self, l: &Object) -> Result<T>;
     fn visit_logical(&mut self, l: &Expr, o: &Token, r: &Expr) -> Result<T>;
-    fn visit_variable(&mut self, n: &Token) -> Result<T>;
+    fn visit_variable(&mut self, n: &Token, s: Option<Slot>) -> Result<T>;
 }
 
 pub trait StatementVisitor {
-    fn visit_block(&mut self, s: &Vec<Box<Stmt>>) -> Result<Return>;
-    fn visit_if(&mut self, c: &Expr, t: &Stmt, e: Option<&Stmt>) -> Result<Return>;
+    fn visit_block(&mut self, s: &[Box<Stmt>]) -> Result<Return>;
+    fn visit_if(&mut self, k: &Token, c: &Expr, t: &Stmt, e: Option<&Stmt>) -> Result<Return>;
     fn visit_print(&mut self, e: &Expr) -> Result<Return>;
     fn visit_expression(&mut self, e: &Expr) -> Result<Return>;
     fn visit_variable(&mut self, n: &Token, i: Option<&Expr>) -> Result<Return>;
-    fn visit_while(&mut self, c: &Expr, o: &Stmt) -> Result<Return>;
-    fn visit_function(&mut self, n: &Token, p: &Vec<Token>, b: &Vec<Box<Stmt>>) -> Result<Return>;
+    fn visit_while(&mut self, k: &Token, c: &Expr, o: &Stmt) -> Result<Return>;
+    fn visit_for(
+        &mut self,
+        t: &Token,
+        i: Option<&Stmt>,
+        c: Option<&Expr>,
+        n: Option<&Expr>,
+        b: &Stmt,
+    ) -> Result<Return>;
+    fn visit_function(&mut self, n: &Token, p: &[Token], b: &[Box<Stmt>]) -> Result<Return>;
     fn visit_return(&mut self, t: &Token, e: Option<&Expr>) -> Result<Return>;
+    fn visit_throw(&mut self, t: &Token, e: &Expr) -> Result<Return>;
+    fn visit_try(
+        &mut self,
+        b: &[Box<Stmt>],
+        c: Option<(&Token, &[Box<Stmt>])>,
+        f: Option<&[Box<Stmt>]>,
+    ) -> Result<Return>;
+    fn visit_import(&mut self, t: &Token, p: &str, n: Option<&[Token]>) -> Result<Return>;
 }
 
+/// Where a variable is stored, as found by `locals::resolve`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Slot {
+    /// In the scope `depth` scopes out from the innermost one, at `index`
+    Local {
+        depth: usize,
+        index: usize,
+    },
+    Global,
+}
+
+// Nodes are boxed inside `Vec`s as well, so every child has the same shape
+#[allow(clippy::vec_box)]
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Expr {
     Assign {
         name: Token,
         value: Box<Expr>,
+        slot: Option<Slot>, // Looked up by name when unresolved
     },
     Binary {
         left: Box<Expr>,
@@ -40,10 +73,22 @@ pub enum Expr {
         paren: Token,
         arguments: Vec<Box<Expr>>,
     },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+    },
     Grouping {
         grouping: Box<Expr>,
     },
-    Literal(Object),
+    Lambda {
+        token: Token, // `fun`, or the `(` of an arrow function
+        params: Vec<Token>,
+        body: Vec<Box<Stmt>>,
+    },
+    Literal {
+        value: Object,
+        line: usize,
+    },
     Logical {
         left: Box<Expr>,
         operator: Token,
@@ -53,13 +98,19 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
-    Variable(Token),
+    Variable {
+        name: Token,
+        slot: Option<Slot>, // Looked up by name when unresolved
+    },
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[allow(clippy::vec_box)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum Stmt {
     Block(Vec<Box<Stmt>>),
     If {
+        token: Token,
         condition: Expr,
         then: Box<Stmt>,
         els: Option<Box<Stmt>>,
@@ -76,13 +127,35 @@ pub enum Stmt {
         initializer: Option<Expr>,
     },
     While {
+        token: Token,
         condition: Expr,
         body: Box<Stmt>,
     },
+    For {
+        token: Token,
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>, // Loops until a `return` when omitted
+        increment: Option<Expr>,
+        body: Box<Stmt>,
+    },
     Return {
         token: Token,
         value: Option<Expr>,
     },
+    Throw {
+        token: Token,
+        value: Expr,
+    },
+    Try {
+        body: Vec<Box<Stmt>>,
+        catch: Option<(Token, Vec<Box<Stmt>>)>, // Bound name and handler
+        finally: Option<Vec<Box<Stmt>>>,
+    },
+    Import {
+        token: Token,
+        path: String,
+        names: Option<Vec<Token>>, // Imports every export when omitted
+    },
 }
 
 impl Stmt {
@@ -93,21 +166,76 @@ impl Stmt {
         match self {
             Self::Block(stmts) => visitor.visit_block(stmts),
             Self::If {
+                token,
                 condition,
                 then,
                 els,
-            } => visitor.visit_if(condition, then, els.as_deref()),
+            } => visitor.visit_if(token, condition, then, els.as_deref()),
             Self::Print(expr) => visitor.visit_print(expr), // TODO: This should be a Stmt::Print (why?)
             Self::Expression(expr) => visitor.visit_expression(expr),
             Self::Function { name, params, body } => visitor.visit_function(name, params, body),
             Self::Return { token, value } => visitor.visit_return(token, value.as_ref()),
+            Self::Throw { token, value } => visitor.visit_throw(token, value),
+            Self::Try {
+                body,
+                catch,
+                finally,
+            } => visitor.visit_try(
+                body,
+                catch
+                    .as_ref()
+                    .map(|(name, handler)| (name, handler.as_slice())),
+                finally.as_deref(),
+            ),
             Self::Var {
                 name,
                 initializer: init,
             } => visitor.visit_variable(name, init.as_ref()),
-            Self::While { condition, body } => visitor.visit_while(condition, body),
+            Self::While {
+                token,
+                condition,
+                body,
+            } => visitor.visit_while(token, condition, body),
+            Self::For {
+                token,
+                initializer,
+                condition,
+                increment,
+                body,
+            } => visitor.visit_for(
+                token,
+                initializer.as_deref(),
+                condition.as_ref(),
+                increment.as_ref(),
+                body,
+            ),
+            Self::Import { token, path, names } => {
+                visitor.visit_import(token, path, names.as_deref())
+            }
         }
     }
+
+    // Line the statement starts on, if any of its tokens carry one
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Self::Block(stmts) | Self::Try { body: stmts, .. } => {
+                stmts.iter().find_map(|stmt| stmt.line())
+            }
+            Self::Expression(expr) | Self::Print(expr) => expr.line(),
+            Self::Function { name, .. } | Self::Var { name, .. } => Some(name.line),
+            Self::If { token, .. }
+            | Self::While { token, .. }
+            | Self::Return { token, .. }
+            | Self::Throw { token, .. }
+            | Self::For { token, .. }
+            | Self::Import { token, .. } => Some(token.line),
+        }
+    }
+
+    /// Name of the statement's kind, e.g. `while`.
+    pub fn kind(&self) -> &'static str {
+        self.into()
+    }
 }
 
 impl Expr {
@@ -116,7 +244,7 @@ impl Expr {
         V: ExpressionVisitor<T>,
     {
         match self {
-            Self::Assign { name, value } => visitor.visit_assignment(name, value),
+            Self::Assign { name, value, slot } => visitor.visit_assignment(name, value, *slot),
             Self::Binary {
                 left,
                 operator,
@@ -127,15 +255,37 @@ impl Expr {
                 paren,
                 arguments,
             } => visitor.visit_call(callee, paren, arguments),
+            Self::Get { object, name } => visitor.visit_get(object, name),
             Self::Grouping { grouping } => visitor.visit_grouping(grouping),
-            Self::Literal(literal) => visitor.visit_literal(literal),
+            Self::Lambda {
+                token,
+                params,
+                body,
+            } => visitor.visit_lambda(token, params, body),
+            Self::Literal { value, .. } => visitor.visit_literal(value),
             Self::Logical {
                 left,
                 operator,
                 right,
             } => visitor.visit_logical(left, operator, right),
             Self::Unary { operator, right } => visitor.visit_unary(operator, right),
-            Self::Variable(name) => visitor.visit_variable(name),
+            Self::Variable { name, slot } => visitor.visit_variable(name, *slot),
+        }
+    }
+
+    // Line of the token that identifies the expression, used to locate errors
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Self::Assign { name, .. } | Self::Get { name, .. } | Self::Variable { name, .. } => {
+                Some(name.line)
+            }
+            Self::Binary { operator, .. }
+            | Self::Logical { operator, .. }
+            | Self::Unary { operator, .. } => Some(operator.line),
+            Self::Call { paren, .. } => Some(paren.line),
+            Self::Lambda { token, .. } => Some(token.line),
+            Self::Grouping { grouping } => grouping.line(),
+            Self::Literal { line, .. } => Some(*line),
         }
     }
 }
@@ -143,7 +293,7 @@ impl Expr {
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Expr::Assign { name, value } => write!(f, "({} = {})", name, value),
+            Expr::Assign { name, value, .. } => write!(f, "({} = {})", name, value),
             Expr::Binary {
                 left,
                 operator,
@@ -154,15 +304,90 @@ impl std::fmt::Display for Expr {
                 operator,
                 right,
             } => write!(f, "({} {} {})", operator, left, right),
-            Expr::Call { callee, .. } => write!(f, "({} (arguments))", callee), // TODO: Clean this up
+            Expr::Call {
+                callee, arguments, ..
+            } => write!(f, "(call {}{})", callee, spaced(arguments)),
+            Expr::Get { object, name } => write!(f, "(. {} {})", object, name),
             Expr::Unary { operator, right } => write!(f, "({} {})", operator, right),
             Expr::Grouping { grouping } => write!(f, "(group {})", grouping),
-            Expr::Literal(l) => write!(f, "{}", l),
-            Expr::Variable(v) => write!(f, "{}", v),
+            Expr::Lambda { params, body, .. } => {
+                write!(f, "(fun ({}){})", spaced(params).trim_start(), spaced(body))
+            }
+            Expr::Literal { value, .. } => write!(f, "{}", value),
+            Expr::Variable { name, .. } => write!(f, "{}", name),
         }
     }
 }
 
+impl std::fmt::Display for Stmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Stmt::Block(stmts) => write!(f, "(block{})", spaced(stmts)),
+            Stmt::If {
+                condition,
+                then,
+                els,
+                ..
+            } => write!(f, "(if {} {}{})", condition, then, spaced(els)),
+            Stmt::Function { name, params, body } => write!(
+                f,
+                "(fun {} ({}){})",
+                name,
+                spaced(params).trim_start(),
+                spaced(body)
+            ),
+            Stmt::Expression(expr) => write!(f, "(expr {})", expr),
+            Stmt::Print(expr) => write!(f, "(print {})", expr),
+            Stmt::Var { name, initializer } => write!(f, "(var {}{})", name, spaced(initializer)),
+            Stmt::While {
+                condition, body, ..
+            } => write!(f, "(while {} {})", condition, body),
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                // Omitted clauses are shown as `_`
+                let clause = |clause: Option<String>| clause.unwrap_or("_".to_string());
+                write!(
+                    f,
+                    "(for {} {} {} {})",
+                    clause(initializer.as_ref().map(|i| i.to_string())),
+                    clause(condition.as_ref().map(|c| c.to_string())),
+                    clause(increment.as_ref().map(|i| i.to_string())),
+                    body
+                )
+            }
+            Stmt::Return { value, .. } => write!(f, "(return{})", spaced(value)),
+            Stmt::Throw { value, .. } => write!(f, "(throw {})", value),
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                write!(f, "(try (block{})", spaced(body))?;
+                if let Some((name, handler)) = catch {
+                    write!(f, " (catch {} (block{}))", name, spaced(handler))?;
+                }
+                if let Some(finally) = finally {
+                    write!(f, " (finally (block{}))", spaced(finally))?;
+                }
+                write!(f, ")")
+            }
+            Stmt::Import { path, names, .. } => {
+                write!(f, "(import \"{}\"{})", path, spaced(names.iter().flatten()))
+            }
+        }
+    }
+}
+
+// Each item preceded by a space
+fn spaced<T: std::fmt::Display>(items: impl IntoIterator<Item = T>) -> String {
+    items.into_iter().map(|item| format!(" {}", item)).collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -174,11 +399,17 @@ mod test {
         let expr = Expr::Binary {
             left: Box::new(Expr::Unary {
                 operator: Token::new(TokenType::Minus, None, 1),
-                right: Box::new(Expr::Literal(Object::Number(123 as f64))),
+                right: Box::new(Expr::Literal {
+                    value: Object::Number(123_f64),
+                    line: 1,
+                }),
             }),
             operator: Token::new(TokenType::Star, None, 1),
             right: Box::new(Expr::Grouping {
-                grouping: Box::new(Expr::Literal(Object::Number(45.67))),
+                grouping: Box::new(Expr::Literal {
+                    value: Object::Number(45.67),
+                    line: 1,
+                }),
             }),
         };
 
@@ -186,4 +417,35 @@ mod test {
 
         assert_eq!(expected, format!("{}", expr));
     }
+
+    #[test]
+    fn display_stmt() {
+        let source =
+            "fun f(a) { if (a) return a; else print nil; } try { throw f(1); } catch (e) {}";
+        let tokens = crate::scanner::Scanner::new(source.to_string())
+            .scan_tokens()
+            .unwrap();
+        let statements = crate::parser::Parser::new(tokens).parse().unwrap();
+
+        let printed: Vec<String> = statements.iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            vec![
+                "(fun f (a) (if a (return a) (print nil)))",
+                "(try (block (throw (call f 1))) (catch e (block)))",
+            ],
+            printed
+        );
+    }
+
+    #[test]
+    fn stmt_line_and_kind() {
+        let source = "var a = 1;\n{\n  while (a) a = nil;\n}";
+        let tokens = crate::scanner::Scanner::new(source.to_string())
+            .scan_tokens()
+            .unwrap();
+        let statements = crate::parser::Parser::new(tokens).parse().unwrap();
+
+        let lines: Vec<_> = statements.iter().map(|s| (s.kind(), s.line())).collect();
+        assert_eq!(vec![("var", Some(1)), ("block", Some(3))], lines);
+    }
 }
@@ -0,0 +1,388 @@
+use crate::ast::{Expr, Stmt};
+use crate::token::Token;
+
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+/// Name of the file for code that wasn't read from one, e.g. `lox -e`.
+pub const UNNAMED: &str = "-";
+
+/// How often the statements and branches of one file ran.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FileCoverage {
+    // Executions of the statements starting on each line
+    pub lines: BTreeMap<usize, u64>,
+    // Times each way of a branch was taken, keyed by the line and column of
+    // its `if`, `while` or `for` keyword or logical operator. The first way
+    // enters the code the branch guards: the `then` branch, the loop body or
+    // the right operand.
+    pub branches: BTreeMap<(usize, usize), [u64; 2]>,
+}
+
+impl FileCoverage {
+    fn register<S: Borrow<Stmt>>(&mut self, statements: &[S]) {
+        for statement in statements {
+            self.register_stmt(statement.borrow());
+        }
+    }
+
+    fn register_stmt(&mut self, statement: &Stmt) {
+        // Blocks and `try` start on their first statement, so aren't counted
+        // separately
+        if !matches!(statement, Stmt::Block(_) | Stmt::Try { .. }) {
+            if let Some(line) = statement.line() {
+                self.lines.entry(line).or_default();
+            }
+        }
+
+        match statement {
+            Stmt::Block(body) | Stmt::Function { body, .. } => self.register(body),
+            Stmt::If {
+                token,
+                condition,
+                then,
+                els,
+            } => {
+                self.register_branch(token);
+                self.register_expr(condition);
+                self.register_stmt(then);
+                if let Some(els) = els {
+                    self.register_stmt(els);
+                }
+            }
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.register_expr(expr),
+            Stmt::Var {
+                initializer: value, ..
+            }
+            | Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.register_expr(value);
+                }
+            }
+            Stmt::Throw { value, .. } => self.register_expr(value),
+            Stmt::While {
+                token,
+                condition,
+                body,
+            } => {
+                self.register_branch(token);
+                self.register_expr(condition);
+                self.register_stmt(body);
+            }
+            Stmt::For {
+                token,
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(initializer) = initializer {
+                    self.register_stmt(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.register_branch(token);
+                    self.register_expr(condition);
+                }
+                if let Some(increment) = increment {
+                    self.register_expr(increment);
+                }
+                self.register_stmt(body);
+            }
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.register(body);
+                if let Some((_, handler)) = catch {
+                    self.register(handler);
+                }
+                if let Some(finally) = finally {
+                    self.register(finally);
+                }
+            }
+            Stmt::Import { .. } => {}
+        }
+    }
+
+    fn register_expr(&mut self, expression: &Expr) {
+        match expression {
+            Expr::Assign { value: expr, .. }
+            | Expr::Get { object: expr, .. }
+            | Expr::Grouping { grouping: expr }
+            | Expr::Unary { right: expr, .. } => self.register_expr(expr),
+            Expr::Binary { left, right, .. } => {
+                self.register_expr(left);
+                self.register_expr(right);
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.register_branch(operator);
+                self.register_expr(left);
+                self.register_expr(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.register_expr(callee);
+                for argument in arguments {
+                    self.register_expr(argument);
+                }
+            }
+            Expr::Lambda { body, .. } => self.register(body),
+            Expr::Literal { .. } | Expr::Variable { .. } => {}
+        }
+    }
+
+    fn register_branch(&mut self, token: &Token) {
+        self.branches.entry((token.line, token.column)).or_default();
+    }
+
+    fn merge(&mut self, other: FileCoverage) {
+        for (line, count) in other.lines {
+            *self.lines.entry(line).or_default() += count;
+        }
+        for (site, [entered, skipped]) in other.branches {
+            let counts = self.branches.entry(site).or_default();
+            counts[0] += entered;
+            counts[1] += skipped;
+        }
+    }
+}
+
+/// Statement and branch coverage of the files a script executed, keyed by
+/// path.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Coverage {
+    files: BTreeMap<PathBuf, FileCoverage>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    /// Adds the statements and branches of `file` with no executions, so the
+    /// ones that never run are reported too.
+    pub fn register<S: Borrow<Stmt>>(&mut self, file: &Path, statements: &[S]) {
+        self.file(file).register(statements);
+    }
+
+    pub fn statement(&mut self, file: &Path, line: usize) {
+        *self.file(file).lines.entry(line).or_default() += 1;
+    }
+
+    /// Records whether the branch at `token` entered the code it guards.
+    pub fn branch(&mut self, file: &Path, token: &Token, entered: bool) {
+        let counts = self
+            .file(file)
+            .branches
+            .entry((token.line, token.column))
+            .or_default();
+        counts[usize::from(!entered)] += 1;
+    }
+
+    fn file(&mut self, file: &Path) -> &mut FileCoverage {
+        if !self.files.contains_key(file) {
+            self.files
+                .insert(file.to_path_buf(), FileCoverage::default());
+        }
+        self.files.get_mut(file).expect("registered file")
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (&Path, &FileCoverage)> {
+        self.files.iter().map(|(path, file)| (path.as_path(), file))
+    }
+
+    /// Adds the executions recorded by `other`, e.g. by another run.
+    pub fn merge(&mut self, other: Coverage) {
+        for (path, file) in other.files {
+            self.files.entry(path).or_default().merge(file);
+        }
+    }
+
+    /// Reads a report written by `write_lcov`.
+    pub fn read_lcov(report: &str) -> Result<Coverage> {
+        let mut coverage = Coverage::new();
+        let mut file = None;
+
+        for (i, line) in report.lines().enumerate() {
+            let error = || anyhow!("invalid lcov record on line {}: {}", i + 1, line);
+            let numbers = |fields: &str| -> Result<Vec<u64>> {
+                fields
+                    .split(',')
+                    .map(|field| match field {
+                        "-" => Ok(0),
+                        field => field.parse().map_err(|_| error()),
+                    })
+                    .collect()
+            };
+
+            match line.split_once(':') {
+                Some(("SF", path)) => file = Some(PathBuf::from(path)),
+                Some(("DA", fields)) => {
+                    let file = coverage.file(file.as_deref().ok_or_else(error)?);
+                    match numbers(fields)?[..] {
+                        [line, count, ..] => *file.lines.entry(line as usize).or_default() += count,
+                        _ => return Err(error()),
+                    }
+                }
+                Some(("BRDA", fields)) => {
+                    let file = coverage.file(file.as_deref().ok_or_else(error)?);
+                    match numbers(fields)?[..] {
+                        [line, column, way @ (0 | 1), taken] => {
+                            let site = (line as usize, column as usize);
+                            file.branches.entry(site).or_default()[way as usize] += taken;
+                        }
+                        _ => return Err(error()),
+                    }
+                }
+                _ if line == "end_of_record" => file = None,
+                // Test names, totals and function records
+                _ => {}
+            }
+        }
+
+        Ok(coverage)
+    }
+
+    /// Writes an lcov tracefile, as read by `genhtml` and `lcov --summary`.
+    /// Branches are numbered by the column they're on.
+    pub fn write_lcov(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for (path, file) in self.files() {
+            writeln!(out, "TN:")?;
+            writeln!(out, "SF:{}", path.display())?;
+
+            for (line, count) in &file.lines {
+                writeln!(out, "DA:{},{}", line, count)?;
+            }
+            let (hit, found) = lines(file);
+            writeln!(out, "LF:{}", found)?;
+            writeln!(out, "LH:{}", hit)?;
+
+            for ((line, column), counts) in &file.branches {
+                for (way, count) in counts.iter().enumerate() {
+                    match counts {
+                        [0, 0] => writeln!(out, "BRDA:{},{},{},-", line, column, way)?,
+                        _ => writeln!(out, "BRDA:{},{},{},{}", line, column, way, count)?,
+                    }
+                }
+            }
+            let (hit, found) = branches(file);
+            writeln!(out, "BRF:{}", found)?;
+            writeln!(out, "BRH:{}", hit)?;
+
+            writeln!(out, "end_of_record")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a table of the lines and branches covered in each file.
+    pub fn write_summary(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let current = std::env::current_dir().unwrap_or_default();
+
+        writeln!(out, "{:<40} {:>15} {:>15}", "file", "lines", "branches")?;
+        for (path, file) in self.files() {
+            let path = path.strip_prefix(&current).unwrap_or(path);
+            writeln!(
+                out,
+                "{:<40} {:>15} {:>15}",
+                path.display(),
+                ratio(lines(file)),
+                ratio(branches(file))
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// Lines that ran, and lines with a statement
+fn lines(file: &FileCoverage) -> (usize, usize) {
+    let hit = file.lines.values().filter(|count| **count > 0).count();
+    (hit, file.lines.len())
+}
+
+// Ways of branches that were taken, and ways of branches
+fn branches(file: &FileCoverage) -> (usize, usize) {
+    let hit = file
+        .branches
+        .values()
+        .flatten()
+        .filter(|count| **count > 0)
+        .count();
+    (hit, file.branches.len() * 2)
+}
+
+fn ratio((hit, found): (usize, usize)) -> String {
+    match found {
+        0 => "-".to_string(),
+        _ => format!(
+            "{}/{} {:>5.1}%",
+            hit,
+            found,
+            hit as f64 * 100.0 / found as f64
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Coverage;
+    use crate::interpreter::Interpreter;
+    use std::path::Path;
+
+    #[test]
+    fn lines_and_branches() {
+        let source = "fun sign(n) {\n  if (n < 0) return -1;\n  return 1;\n}\nvar a = sign(2) > 0 or sign(3) > 0;\nwhile (false) print a;\n";
+        let tokens = crate::scanner::Scanner::new(source.to_string())
+            .scan_tokens()
+            .unwrap();
+        let statements = crate::parser::Parser::new(tokens).parse().unwrap();
+
+        let mut interpreter = Interpreter::new().with_coverage();
+        interpreter.interpret(statements).unwrap();
+        let coverage = interpreter.coverage().unwrap();
+
+        let mut report = Vec::new();
+        coverage.write_lcov(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        let expected = "\
+TN:
+SF:-
+DA:1,1
+DA:2,1
+DA:3,1
+DA:5,1
+DA:6,1
+LF:5
+LH:5
+BRDA:2,2,0,0
+BRDA:2,2,1,1
+BRDA:5,20,0,0
+BRDA:5,20,1,1
+BRDA:6,0,0,0
+BRDA:6,0,1,1
+BRF:6
+BRH:3
+end_of_record
+";
+        assert_eq!(expected, report);
+
+        // Merging a second run doubles the counts
+        let mut merged = Coverage::read_lcov(&report).unwrap();
+        merged.merge(coverage.clone());
+        let file = merged.files().next().unwrap();
+        assert_eq!(Path::new("-"), file.0);
+        assert_eq!(Some(&2), file.1.lines.get(&3));
+        assert_eq!(Some(&[0, 2]), file.1.branches.get(&(2, 2)));
+    }
+}
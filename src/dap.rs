@@ -0,0 +1,468 @@
+// A Debug Adapter Protocol server over stdio, see
+// https://microsoft.github.io/debug-adapter-protocol/. The script runs on the
+// adapter's thread, which answers requests while it's paused. Lines and
+// columns count from 1, and breakpoints can only be set in the launched script.
+
+use crate::ast::Stmt;
+use crate::debugger::{parse_expression, variables, Controller, Debugger, Step, Stop};
+use crate::error::Exit;
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::rpc;
+use crate::scanner::Scanner;
+
+use std::cell::RefCell;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+
+// Id of the only thread
+const THREAD: u64 = 1;
+
+/// Answers requests read from `input`, running the script of a `launch`
+/// request once the client is done configuring it, until `disconnect`.
+pub fn serve(input: impl BufRead + 'static, output: impl Write + 'static) -> Result<()> {
+    let connection = Rc::new(RefCell::new(Connection {
+        input: Box::new(input),
+        output: Box::new(output),
+        seq: 0,
+        printed: String::new(),
+        disconnected: false,
+    }));
+    let mut adapter = Adapter {
+        connection: connection.clone(),
+        controller: Controller::new(),
+        program: None,
+        entry: false,
+        references: Vec::new(),
+    };
+
+    let mut launch = None;
+    let mut configured = false;
+    while launch.is_none() || !configured {
+        let Some(request) = connection.borrow_mut().read()? else {
+            return Ok(());
+        };
+
+        let result = match request["command"].as_str().unwrap_or_default() {
+            "initialize" => {
+                let capabilities = json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsConditionalBreakpoints": true,
+                    "supportsEvaluateForHovers": true,
+                });
+                let mut connection = connection.borrow_mut();
+                connection.respond(&request, Ok(capabilities))?;
+                connection.event("initialized", Value::Null)?;
+                continue;
+            }
+            // Without a program the launch fails, and another is awaited
+            "launch" => {
+                let arguments = &request["arguments"];
+                arguments["program"]
+                    .as_str()
+                    .context("launch without a program")
+                    .map(|program| {
+                        adapter.program = Some(PathBuf::from(program));
+                        adapter.entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+                        launch = Some(arguments.clone());
+                        Value::Null
+                    })
+            }
+            "configurationDone" => {
+                configured = true;
+                Ok(Value::Null)
+            }
+            "disconnect" => return connection.borrow_mut().respond(&request, Ok(Value::Null)),
+            _ => adapter.request(None, &request),
+        };
+        connection.borrow_mut().respond(&request, result)?;
+    }
+
+    let launch = launch.expect("launch request");
+    let code = run(adapter, &launch)?;
+
+    let mut connection = connection.borrow_mut();
+    if connection.disconnected {
+        return Ok(());
+    }
+    connection.flush_printed()?;
+    connection.event("exited", json!({ "exitCode": code }))?;
+    connection.event("terminated", Value::Null)?;
+
+    while !connection.disconnected {
+        let Some(request) = connection.read()? else {
+            break;
+        };
+        match request["command"].as_str() {
+            Some("disconnect") => return connection.respond(&request, Ok(Value::Null)),
+            _ => connection.respond(&request, Err(anyhow!("the script has exited")))?,
+        }
+    }
+
+    Ok(())
+}
+
+// Runs the launched script, returning its exit code
+fn run(mut adapter: Adapter, launch: &Value) -> Result<i32> {
+    let connection = adapter.connection.clone();
+    let program = adapter.program.clone().expect("launched program");
+    let arguments = launch["args"]
+        .as_array()
+        .map(|args| {
+            args.iter()
+                .filter_map(|arg| arg.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !adapter.entry {
+        adapter.controller.resume(Step::Continue);
+    }
+
+    let result = std::fs::read_to_string(&program)
+        .with_context(|| format!("unable to read {}", program.display()))
+        .and_then(|source| Scanner::new(source).scan_tokens())
+        .and_then(|tokens| Parser::new(tokens).parse())
+        .and_then(|statements| {
+            Interpreter::new()
+                .with_arguments(arguments)
+                .with_script(&program)
+                .with_output(Printed(connection.clone()))
+                .with_debugger(adapter)
+                .interpret(statements)
+        });
+
+    match result {
+        Ok(()) => Ok(0),
+        Err(error) => match error.downcast_ref() {
+            Some(&Exit(code)) => Ok(code),
+            None => {
+                let mut connection = connection.borrow_mut();
+                connection.flush_printed()?;
+                let output = format!("{:#}\n", error);
+                connection.event("output", json!({"category": "stderr", "output": output}))?;
+                Ok(1)
+            }
+        },
+    }
+}
+
+struct Connection {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: u64,
+    // What the script printed since the last complete line
+    printed: String,
+    disconnected: bool,
+}
+
+impl Connection {
+    fn read(&mut self) -> Result<Option<Value>> {
+        rpc::read(&mut self.input)
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        rpc::write(&mut self.output, &message)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value>) -> Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(error) => response["message"] = json!(error.to_string()),
+        }
+        if request["command"] == "disconnect" {
+            self.disconnected = true;
+        }
+
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Result<()> {
+        let mut message = json!({"type": "event", "event": event});
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        self.send(message)
+    }
+
+    // Sends what the script printed as an `output` event per line
+    fn print(&mut self, text: &str) -> Result<()> {
+        self.printed.push_str(text);
+        while let Some(end) = self.printed.find('\n') {
+            let line: String = self.printed.drain(..=end).collect();
+            self.event("output", json!({"category": "stdout", "output": line}))?;
+        }
+        Ok(())
+    }
+
+    fn flush_printed(&mut self) -> Result<()> {
+        if self.printed.is_empty() {
+            return Ok(());
+        }
+        let output = std::mem::take(&mut self.printed);
+        self.event("output", json!({"category": "stdout", "output": output}))
+    }
+}
+
+// Where the launched script's `print` writes
+struct Printed(Rc<RefCell<Connection>>);
+
+impl Write for Printed {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .borrow_mut()
+            .print(&String::from_utf8_lossy(buf))
+            .map_err(std::io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Adapter {
+    connection: Rc<RefCell<Connection>>,
+    controller: Controller,
+    program: Option<PathBuf>,
+    // Whether the next pause is the one on entry
+    entry: bool,
+    // Frame and scope of each `variablesReference` handed out while paused,
+    // where reference `n` is at index `n - 1`
+    references: Vec<(usize, usize)>,
+}
+
+impl Adapter {
+    // Answers a request that doesn't resume the script. Inspecting it is only
+    // possible while paused, when `interpreter` is given.
+    fn request(&mut self, interpreter: Option<&mut Interpreter>, request: &Value) -> Result<Value> {
+        let arguments = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+
+        match (command, interpreter) {
+            ("setBreakpoints", _) => self.set_breakpoints(arguments),
+            ("threads", _) => Ok(json!({"threads": [{"id": THREAD, "name": "main"}]})),
+            ("stackTrace", _) => {
+                let source = self.source();
+                let frames: Vec<Value> = self
+                    .controller
+                    .stack()
+                    .enumerate()
+                    .map(|(id, frame)| {
+                        json!({
+                            "id": id,
+                            "name": frame.name,
+                            "line": frame.line,
+                            "column": 1,
+                            "source": source,
+                        })
+                    })
+                    .collect();
+                Ok(json!({"totalFrames": frames.len(), "stackFrames": frames}))
+            }
+            ("scopes", Some(interpreter)) => {
+                let frame = index(&arguments["frameId"])?;
+                let scopes = variables(interpreter, frame);
+                let globals = scopes.len().saturating_sub(1);
+
+                let scopes: Vec<Value> = (0..scopes.len())
+                    .map(|depth| {
+                        let name = match depth {
+                            _ if depth == globals => "Globals".to_string(),
+                            0 => "Locals".to_string(),
+                            _ => format!("Scope {}", depth),
+                        };
+                        self.references.push((frame, depth));
+                        json!({
+                            "name": name,
+                            "variablesReference": self.references.len(),
+                            "expensive": false,
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "scopes": scopes }))
+            }
+            ("variables", Some(interpreter)) => {
+                let reference = index(&arguments["variablesReference"])?;
+                let (frame, depth) = reference
+                    .checked_sub(1)
+                    .and_then(|i| self.references.get(i))
+                    .copied()
+                    .context("unknown variablesReference")?;
+
+                let variables: Vec<Value> = variables(interpreter, frame)
+                    .into_iter()
+                    .nth(depth)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(name, value)| {
+                        json!({"name": name, "value": value.to_string(), "variablesReference": 0})
+                    })
+                    .collect();
+                Ok(json!({ "variables": variables }))
+            }
+            ("evaluate", Some(interpreter)) => {
+                if arguments["frameId"].as_u64().unwrap_or(0) != 0 {
+                    return Err(anyhow!(
+                        "expressions can only be evaluated in the top frame"
+                    ));
+                }
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                let value = interpreter.evaluate(&parse_expression(expression)?)?;
+                Ok(json!({"result": value.to_string(), "variablesReference": 0}))
+            }
+            ("scopes" | "variables" | "evaluate", None) => Err(anyhow!("the script isn't paused")),
+            _ => Err(anyhow!("unsupported request '{}'", command)),
+        }
+    }
+
+    // Replaces the breakpoints of the launched script
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value> {
+        let path = arguments["source"]["path"].as_str().map(Path::new);
+        let program = match (&self.program, path) {
+            (Some(program), Some(path)) => same_file(program, path),
+            _ => true,
+        };
+        if program {
+            self.controller.clear_breakpoints();
+        }
+
+        let breakpoints = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let breakpoints: Vec<Value> = breakpoints
+            .iter()
+            .map(|breakpoint| {
+                let line = breakpoint["line"].as_u64().unwrap_or(0) as usize;
+                let condition = match breakpoint["condition"].as_str() {
+                    Some(condition) if !condition.trim().is_empty() => {
+                        parse_expression(condition).map(Some)
+                    }
+                    _ => Ok(None),
+                };
+
+                match (program, condition) {
+                    (false, _) => json!({
+                        "verified": false,
+                        "line": line,
+                        "message": "breakpoints can only be set in the launched script",
+                    }),
+                    (true, Ok(condition)) => {
+                        self.controller.set_breakpoint(line, condition);
+                        json!({"verified": true, "line": line})
+                    }
+                    (true, Err(error)) => {
+                        json!({"verified": false, "line": line, "message": error.to_string()})
+                    }
+                }
+            })
+            .collect();
+
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn source(&self) -> Value {
+        match &self.program {
+            Some(program) => json!({
+                "name": program.file_name().map(|name| name.to_string_lossy()),
+                "path": program,
+            }),
+            None => Value::Null,
+        }
+    }
+}
+
+impl Debugger for Adapter {
+    fn statement(&mut self, interpreter: &mut Interpreter, statement: &Stmt) -> Result<()> {
+        let Some(stop) = self.controller.pause(interpreter, statement) else {
+            return Ok(());
+        };
+        let reason = match stop {
+            Stop::Step if std::mem::take(&mut self.entry) => "entry",
+            Stop::Step => "step",
+            Stop::Breakpoint => "breakpoint",
+        };
+        self.references.clear();
+
+        let mut connection = self.connection.borrow_mut();
+        connection.flush_printed()?;
+        connection.event(
+            "stopped",
+            json!({"reason": reason, "threadId": THREAD, "allThreadsStopped": true}),
+        )?;
+        drop(connection);
+
+        loop {
+            // Not borrowed while handling the request, as evaluating can print
+            let Some(request) = self.connection.borrow_mut().read()? else {
+                return Err(Exit(0).into());
+            };
+
+            let (step, body) = match request["command"].as_str().unwrap_or_default() {
+                "continue" => (Step::Continue, json!({"allThreadsContinued": true})),
+                "next" => (Step::Over, Value::Null),
+                "stepIn" => (Step::In, Value::Null),
+                "stepOut" => (Step::Out, Value::Null),
+                "disconnect" => {
+                    self.connection
+                        .borrow_mut()
+                        .respond(&request, Ok(Value::Null))?;
+                    return Err(Exit(0).into());
+                }
+                _ => {
+                    let result = self.request(Some(interpreter), &request);
+                    self.connection.borrow_mut().respond(&request, result)?;
+                    continue;
+                }
+            };
+
+            self.connection.borrow_mut().respond(&request, Ok(body))?;
+            self.controller.resume(step);
+            return Ok(());
+        }
+    }
+
+    fn enter(&mut self, name: &str) {
+        self.controller.enter(name);
+    }
+
+    fn exit(&mut self) {
+        self.controller.exit();
+    }
+}
+
+impl std::fmt::Debug for Adapter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Adapter")
+            .field("controller", &self.controller)
+            .field("program", &self.program)
+            .finish_non_exhaustive()
+    }
+}
+
+fn index(value: &Value) -> Result<usize> {
+    value
+        .as_u64()
+        .map(|index| index as usize)
+        .context("expected a number")
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
@@ -1,11 +1,15 @@
 use anyhow::Result;
 
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::path::Path;
 use std::rc::Rc;
 
 use crate::ast::Stmt;
 use crate::environment::Environment;
 use crate::error::EvaluationError;
+use crate::gc::{Trace, Tracer};
+use crate::heap::{HeapSize, Visited};
 use crate::interpreter::Interpreter;
 use crate::token::Token;
 
@@ -13,9 +17,26 @@ use crate::token::Token;
 pub enum Object {
     Nil,
     Boolean(bool),
-    String(String),
+    String(Rc<str>), // Immutable, so copies share the text
     Number(f64),
     Function(Rc<dyn Callable>),
+    Error(Rc<ErrorValue>),
+    Namespace(Rc<Namespace>),
+    List(Rc<Vec<Object>>),
+}
+
+// A runtime error raised by the interpreter, as seen by a `catch` clause
+#[derive(Debug, PartialEq)]
+pub struct ErrorValue {
+    pub message: String,
+    pub line: Option<usize>,
+}
+
+// A named group of values, e.g. the `math` natives, read with `.`
+#[derive(Debug)]
+pub struct Namespace {
+    pub name: String,
+    pub members: HashMap<String, Object>,
 }
 
 impl PartialEq for Object {
@@ -23,8 +44,12 @@ impl PartialEq for Object {
         match (self, other) {
             (Self::Nil, Self::Nil) => true,
             (Self::Boolean(ref l), Self::Boolean(ref r)) => l == r,
-            (Self::String(ref l), Self::String(ref r)) => l == r,
+            // Strings sharing their text, like interned literals, skip comparing it
+            (Self::String(ref l), Self::String(ref r)) => Rc::ptr_eq(l, r) || l == r,
             (Self::Number(ref l), Self::Number(ref r)) => l == r,
+            (Self::Error(ref l), Self::Error(ref r)) => Rc::ptr_eq(l, r),
+            (Self::Namespace(ref l), Self::Namespace(ref r)) => Rc::ptr_eq(l, r),
+            (Self::List(ref l), Self::List(ref r)) => l == r,
             (_, _) => false,
         }
     }
@@ -40,9 +65,66 @@ impl Hash for Object {
     }
 }
 
+// Strings and errors hold no other values
+impl Trace for Object {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Self::Function(f) => tracer.visit(f, None, |tracer| f.trace(tracer)),
+            Self::List(l) => tracer.visit(l, None, |tracer| {
+                l.iter().for_each(|value| value.trace(tracer))
+            }),
+            Self::Namespace(n) => tracer.visit(n, None, |tracer| {
+                n.members.values().for_each(|value| value.trace(tracer))
+            }),
+            _ => {}
+        }
+    }
+}
+
+impl HeapSize for Object {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        let retained = match self {
+            Self::String(s) if visited.first_visit(Rc::as_ptr(s)) => s.len(),
+            Self::Function(f) if visited.first_visit(Rc::as_ptr(f)) => f.heap_size(visited),
+            Self::Error(e) if visited.first_visit(Rc::as_ptr(e)) => {
+                std::mem::size_of::<ErrorValue>() + e.message.capacity()
+            }
+            Self::Namespace(n) if visited.first_visit(Rc::as_ptr(n)) => {
+                let members: usize = n
+                    .members
+                    .iter()
+                    .map(|(name, value)| name.capacity() + value.heap_size(visited))
+                    .sum();
+                std::mem::size_of::<Namespace>() + members
+            }
+            Self::List(l) if visited.first_visit(Rc::as_ptr(l)) => {
+                let elements: usize = l.iter().map(|value| value.heap_size(visited)).sum();
+                std::mem::size_of::<Vec<Object>>() + elements
+            }
+            _ => 0,
+        };
+
+        std::mem::size_of::<Object>() + retained
+    }
+}
+
 impl Object {
+    /// Name of the value's type, as returned by the `type` native.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Nil => "nil",
+            Self::Boolean(_) => "boolean",
+            Self::Number(_) => "number",
+            Self::String(_) => "string",
+            Self::Function(_) => "function",
+            Self::Error(_) => "error",
+            Self::Namespace(_) => "namespace",
+            Self::List(_) => "list",
+        }
+    }
+
     pub fn is_truthy(&self) -> bool {
-        match !!&*self {
+        match !!self {
             Self::Boolean(true) => true,
             Self::Boolean(false) => false,
             _ => unreachable!(),
@@ -68,48 +150,90 @@ impl Return {
 
 pub trait Callable: std::fmt::Debug {
     fn arity(&self) -> u8; // Max 255 arguments
-    fn call(&self, i: &mut Interpreter, arguments: &Vec<Object>) -> Result<Return>;
+    fn call(&self, i: &mut Interpreter, arguments: &[Object]) -> Result<Return>;
+
+    // Name reported by the profiler
+    fn name(&self) -> String {
+        "<native>".to_string()
+    }
+
+    // Memory retained by the callable itself, see `HeapSize`
+    fn heap_size(&self, _visited: &mut Visited) -> usize {
+        0
+    }
+
+    // Values held by the callable itself, see `Trace`
+    fn trace(&self, _tracer: &mut Tracer) {}
 }
 
-#[derive(Debug, PartialEq)]
+#[allow(clippy::vec_box)]
 pub struct Function {
     name: Token, // Identifier
     params: Vec<Token>,
     body: Vec<Box<Stmt>>, // Block
     closure: Environment,
+    // Script or module the function was defined in
+    file: Option<Rc<Path>>,
 }
 
 impl Function {
+    #[allow(clippy::vec_box)]
     pub fn new(
         name: Token,
         params: Vec<Token>,
         body: Vec<Box<Stmt>>,
         closure: Environment,
+        file: Option<Rc<Path>>,
     ) -> Function {
         Function {
             name,
             params,
             body,
             closure,
+            file,
         }
     }
 }
 
+// The closure usually contains the function itself, so it is left out to
+// avoid recursing forever.
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Callable for Function {
     fn arity(&self) -> u8 {
         self.params.len() as u8
     }
-    fn call(&self, interpreter: &mut Interpreter, arguments: &Vec<Object>) -> Result<Return> {
+    fn call(&self, interpreter: &mut Interpreter, arguments: &[Object]) -> Result<Return> {
         let mut environment = Environment::contains(&self.closure);
 
         for (param, arg) in self.params.iter().zip(arguments.iter()) {
-            let param = param.lexeme.clone().unwrap(); // TODO: Danger zone
+            let param = param.symbol.expect("parameter name");
             environment.define(param, arg.clone());
         }
 
-        let mut interpreter = interpreter.with_environment(environment);
+        interpreter.execute_function(&self.name(), self.file.clone(), environment, &self.body)
+    }
+
+    fn name(&self) -> String {
+        match &self.name.token_type {
+            crate::token::TokenType::Identifier(name) => name.clone(),
+            _ => format!("<lambda:{}>", self.name.line),
+        }
+    }
+
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        std::mem::size_of::<Function>() + self.closure.heap_size(visited)
+    }
 
-        interpreter.execute_block(&self.body)
+    fn trace(&self, tracer: &mut Tracer) {
+        self.closure.trace(tracer);
     }
 }
 
@@ -120,7 +244,10 @@ impl Callable for Clock {
     fn arity(&self) -> u8 {
         0
     }
-    fn call(&self, _: &mut Interpreter, _: &Vec<Object>) -> Result<Return> {
+    fn name(&self) -> String {
+        "clock".to_string()
+    }
+    fn call(&self, _: &mut Interpreter, _: &[Object]) -> Result<Return> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::SystemTime::UNIX_EPOCH)
             .expect("SystemTime before 1970-01-01 00:00:00 UTC");
@@ -136,7 +263,7 @@ impl std::ops::Add for Object {
     fn add(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Self::Number(a), Self::Number(b)) => Ok(Self::Number(a + b)),
-            (Self::String(a), Self::String(b)) => Ok(Self::String(a + &b)),
+            (Self::String(a), Self::String(b)) => Ok(Self::String([&*a, &*b].concat().into())),
             (Self::String(_), _) | (_, Self::String(_)) => {
                 Err(EvaluationError::StringConcatination.into())
             }
@@ -218,6 +345,12 @@ impl std::fmt::Display for Object {
             Self::Boolean(ref b) => write!(f, "{}", b),
             Self::Nil => write!(f, "nil"),
             Self::Function(_) => write!(f, "<fn placehodler>"),
+            Self::Error(ref e) => write!(f, "{}", e.message),
+            Self::Namespace(ref n) => write!(f, "<namespace {}>", n.name),
+            Self::List(ref l) => {
+                let elements: Vec<String> = l.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
         }
     }
 }
@@ -240,11 +373,30 @@ mod test {
 
         // Truthy
         assert!(Boolean(true).is_truthy());
-        assert!(String("something".to_string()).is_truthy());
+        assert!(String("something".into()).is_truthy());
         assert!(Number(1.23).is_truthy());
 
         // Falsy
         assert!(!Nil.is_truthy());
         assert!(!Boolean(false).is_truthy());
     }
+
+    #[test]
+    fn shared_strings() {
+        use super::Object;
+        use crate::heap::{HeapSize, Visited};
+        use std::rc::Rc;
+
+        let a: Rc<str> = "lox".into();
+        let b = a.clone();
+        assert_eq!(Object::String(a.clone()), Object::String("lox".into()));
+
+        // The text is counted once, however many values share it
+        let list = Object::List(Rc::new(vec![Object::String(a), Object::String(b)]));
+        let object = std::mem::size_of::<Object>();
+        assert_eq!(
+            object + std::mem::size_of::<Vec<Object>>() + 2 * object + 3,
+            list.heap_size(&mut Visited::new())
+        );
+    }
 }
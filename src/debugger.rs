@@ -0,0 +1,448 @@
+use crate::ast::{Expr, Stmt};
+use crate::data_types::Object;
+use crate::error::Exit;
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Result};
+
+// Name of the frame for code outside of any function
+const SCRIPT: &str = "<script>";
+
+/// Hooks called by an interpreter with a debugger attached, see
+/// `Interpreter::with_debugger`.
+pub trait Debugger: std::fmt::Debug {
+    /// Called before each statement other than blocks. The debugger isn't
+    /// attached while this runs, so it can evaluate code without reentering.
+    fn statement(&mut self, interpreter: &mut Interpreter, statement: &Stmt) -> Result<()>;
+
+    /// Called when a Lox function or an imported module starts executing.
+    fn enter(&mut self, name: &str);
+
+    /// Called when the last entered function or module finishes, however it
+    /// finishes.
+    fn exit(&mut self);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// Run until a breakpoint
+    Continue,
+    /// Pause at the next statement, including in called functions
+    In,
+    /// Pause at the next statement of this function or its callers
+    Over,
+    /// Pause at the next statement of a caller
+    Out,
+}
+
+/// Why execution paused.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    Step,
+    Breakpoint,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub name: String,
+    // Line of the statement executing in this frame, 0 before the first
+    pub line: usize,
+}
+
+/// Decides where execution pauses, from breakpoints and the last step
+/// requested, and tracks the Lox call stack to do so.
+#[derive(Debug)]
+pub struct Controller {
+    // Lines with a breakpoint, with the condition it has if any
+    breakpoints: BTreeMap<usize, Option<Expr>>,
+    step: Step,
+    // Call depth when the step was requested
+    depth: usize,
+    stack: Vec<Frame>,
+}
+
+impl Controller {
+    /// A controller that pauses before the first statement.
+    pub fn new() -> Self {
+        Controller {
+            breakpoints: BTreeMap::new(),
+            step: Step::In,
+            depth: 1,
+            stack: vec![Frame {
+                name: SCRIPT.to_string(),
+                line: 0,
+            }],
+        }
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize, condition: Option<Expr>) {
+        self.breakpoints.insert(line, condition);
+    }
+
+    /// Removes the breakpoint on `line`, returning whether there was one.
+    pub fn clear_breakpoint(&mut self, line: usize) -> bool {
+        self.breakpoints.remove(&line).is_some()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, Option<&Expr>)> {
+        self.breakpoints
+            .iter()
+            .map(|(line, condition)| (*line, condition.as_ref()))
+    }
+
+    /// Resumes execution until `step` completes or a breakpoint is hit.
+    pub fn resume(&mut self, step: Step) {
+        self.step = step;
+        self.depth = self.stack.len();
+    }
+
+    /// The call stack, innermost frame first.
+    pub fn stack(&self) -> impl Iterator<Item = &Frame> {
+        self.stack.iter().rev()
+    }
+
+    /// Records that `statement` is about to run, returning why execution
+    /// should pause before it, if it should. A breakpoint's condition that
+    /// fails to evaluate pauses, so the error can be inspected.
+    pub fn pause(&mut self, interpreter: &mut Interpreter, statement: &Stmt) -> Option<Stop> {
+        let line = statement.line()?;
+        let frame = self.stack.last_mut().expect("script frame");
+        frame.line = line;
+
+        let depth = self.stack.len();
+        let stepped = match self.step {
+            Step::Continue => false,
+            Step::In => true,
+            Step::Over => depth <= self.depth,
+            Step::Out => depth < self.depth,
+        };
+        if stepped {
+            return Some(Stop::Step);
+        }
+
+        match self.breakpoints.get(&line)? {
+            Some(condition) => interpreter
+                .evaluate(condition)
+                .map_or(true, |value| value.is_truthy())
+                .then_some(Stop::Breakpoint),
+            None => Some(Stop::Breakpoint),
+        }
+    }
+
+    pub fn enter(&mut self, name: &str) {
+        self.stack.push(Frame {
+            name: name.to_string(),
+            line: 0,
+        });
+    }
+
+    pub fn exit(&mut self) {
+        if self.stack.len() > 1 {
+            self.stack.pop();
+        }
+    }
+}
+
+/// Parses a Lox expression typed into the debugger.
+pub fn parse_expression(source: &str) -> Result<Expr> {
+    let tokens = Scanner::new(format!("{};", source)).scan_tokens()?;
+    let mut statements = Parser::new(tokens).parse()?;
+
+    match (statements.pop(), statements.is_empty()) {
+        (Some(Stmt::Expression(expression)), true) => Ok(*expression),
+        _ => Err(anyhow!("expected a single expression")),
+    }
+}
+
+/// The variables of a frame's scopes, innermost first, leaving out the
+/// natives.
+pub fn variables(interpreter: &Interpreter, frame: usize) -> Vec<Vec<(String, Object)>> {
+    let builtins = Interpreter::new().global_names();
+    let Some(scopes) = interpreter.scopes(frame) else {
+        return Vec::new();
+    };
+
+    scopes
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(depth, scope)| {
+            let mut variables: Vec<(String, Object)> = scope
+                .iter()
+                .filter(|(name, _)| depth > 0 || !builtins.contains(name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            variables.sort_by(|a, b| a.0.cmp(&b.0));
+            variables
+        })
+        .collect()
+}
+
+const HELP: &str = "\
+break LINE [if CONDITION]  pause before LINE runs, when CONDITION is truthy
+break                      list the breakpoints
+delete LINE                remove the breakpoint on LINE
+continue                   run until a breakpoint
+step                       run to the next statement, entering calls
+next                       run to the next statement, stepping over calls
+finish                     run until the current function returns
+print EXPRESSION           evaluate EXPRESSION in the current scope
+scopes [FRAME]             list the variables of each scope of a frame
+backtrace                  list the call stack
+quit                       stop the script
+Commands other than scopes can be shortened to their first letter, or bt for
+backtrace, and an empty line repeats the last command.";
+
+/// A debugger driven by commands read from a terminal, see `lox debug`.
+pub struct Console<R, W> {
+    controller: Controller,
+    input: R,
+    output: W,
+    source: Vec<String>,
+    last: String,
+}
+
+impl<R: BufRead, W: Write> Console<R, W> {
+    /// Debugs `source`, reading commands from `input` and writing to `output`.
+    pub fn new(source: &str, input: R, output: W) -> Self {
+        Console {
+            controller: Controller::new(),
+            input,
+            output,
+            source: source.lines().map(str::to_string).collect(),
+            last: String::new(),
+        }
+    }
+
+    // Reads and runs commands until one resumes execution
+    fn prompt(&mut self, interpreter: &mut Interpreter, stop: Stop) -> Result<()> {
+        let frame = self.controller.stack().next().expect("script frame");
+        let reason = match stop {
+            Stop::Step => "Paused",
+            Stop::Breakpoint => "Breakpoint",
+        };
+        writeln!(
+            self.output,
+            "{} at line {} in {}",
+            reason, frame.line, frame.name
+        )?;
+        self.list(frame.line)?;
+
+        loop {
+            write!(self.output, "(lox) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                // Nobody to resume later, so run the rest of the script
+                self.controller.clear_breakpoints();
+                self.controller.resume(Step::Continue);
+                return Ok(());
+            }
+
+            let line = match line.trim() {
+                "" => self.last.clone(),
+                line => line.to_string(),
+            };
+            self.last = line.clone();
+
+            let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+            let argument = argument.trim();
+            let step = match command {
+                "c" | "continue" => Step::Continue,
+                "s" | "step" => Step::In,
+                "n" | "next" => Step::Over,
+                "f" | "finish" => Step::Out,
+                "q" | "quit" => return Err(Exit(0).into()),
+                _ => {
+                    if let Err(error) = self.command(interpreter, command, argument) {
+                        writeln!(self.output, "{}", error)?;
+                    }
+                    continue;
+                }
+            };
+
+            self.controller.resume(step);
+            return Ok(());
+        }
+    }
+
+    // Runs a command that doesn't resume execution
+    fn command(
+        &mut self,
+        interpreter: &mut Interpreter,
+        command: &str,
+        argument: &str,
+    ) -> Result<()> {
+        match command {
+            "b" | "break" if argument.is_empty() => {
+                for (line, condition) in self.controller.breakpoints() {
+                    match condition {
+                        Some(condition) => writeln!(self.output, "line {} if {}", line, condition)?,
+                        None => writeln!(self.output, "line {}", line)?,
+                    }
+                }
+            }
+            "b" | "break" => {
+                let (line, condition) = match argument.split_once(" if ") {
+                    Some((line, condition)) => (line, Some(parse_expression(condition)?)),
+                    None => (argument, None),
+                };
+                let line = line.trim().parse()?;
+                self.controller.set_breakpoint(line, condition);
+                writeln!(self.output, "Breakpoint on line {}", line)?;
+            }
+            "d" | "delete" => {
+                if !self.controller.clear_breakpoint(argument.parse()?) {
+                    writeln!(self.output, "No breakpoint on line {}", argument)?;
+                }
+            }
+            "p" | "print" => {
+                let value = interpreter.evaluate(&parse_expression(argument)?)?;
+                writeln!(self.output, "{}", value)?;
+            }
+            "scopes" => {
+                let frame = match argument {
+                    "" => 0,
+                    frame => frame.parse()?,
+                };
+                let scopes = variables(interpreter, frame);
+                if scopes.is_empty() {
+                    return Err(anyhow!("no frame {}", frame));
+                }
+                let globals = scopes.len() - 1;
+                for (depth, variables) in scopes.into_iter().enumerate() {
+                    match depth {
+                        _ if depth == globals => writeln!(self.output, "globals:")?,
+                        _ => writeln!(self.output, "scope {}:", depth)?,
+                    }
+                    for (name, value) in variables {
+                        writeln!(self.output, "  {} = {}", name, value)?;
+                    }
+                }
+            }
+            "bt" | "backtrace" => {
+                for (i, frame) in self.controller.stack().enumerate() {
+                    writeln!(self.output, "#{} {} at line {}", i, frame.name, frame.line)?;
+                }
+            }
+            "h" | "help" => writeln!(self.output, "{}", HELP)?,
+            _ => return Err(anyhow!("unknown command '{}', see 'help'", command)),
+        }
+
+        Ok(())
+    }
+
+    fn list(&mut self, line: usize) -> Result<()> {
+        if let Some(source) = line.checked_sub(1).and_then(|i| self.source.get(i)) {
+            writeln!(self.output, "{:>4} | {}", line, source)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead, W: Write> Debugger for Console<R, W> {
+    fn statement(&mut self, interpreter: &mut Interpreter, statement: &Stmt) -> Result<()> {
+        match self.controller.pause(interpreter, statement) {
+            Some(stop) => self.prompt(interpreter, stop),
+            None => Ok(()),
+        }
+    }
+
+    fn enter(&mut self, name: &str) {
+        self.controller.enter(name);
+    }
+
+    fn exit(&mut self) {
+        self.controller.exit();
+    }
+}
+
+impl<R, W> std::fmt::Debug for Console<R, W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Console")
+            .field("controller", &self.controller)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Console;
+    use crate::interpreter::Interpreter;
+
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    // Output shared with the test after the console is moved into the
+    // interpreter
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn session() {
+        let source = "fun add(a, b) {\n  var sum = a + b;\n  return sum;\n}\nvar total = 0;\nfor (var i = 0; i < 3; i = i + 1) {\n  total = add(total, i);\n}\nprint total;\n";
+        let commands = "break 3 if a > 0\nc\np sum * 10\nscopes\nbt\nbreak 1\ndelete 3\nbreak\nfinish\np total\nc\n";
+
+        let output = Shared::default();
+        let program = Shared::default();
+        let console = Console::new(source, commands.as_bytes(), output.clone());
+        let mut interpreter = Interpreter::new()
+            .with_debugger(console)
+            .with_output(program.clone());
+
+        let tokens = crate::scanner::Scanner::new(source.to_string())
+            .scan_tokens()
+            .unwrap();
+        let statements = crate::parser::Parser::new(tokens).parse().unwrap();
+        interpreter.interpret(statements).unwrap();
+
+        let output = String::from_utf8(output.0.take()).unwrap();
+        let expected = "\
+Paused at line 1 in <script>
+   1 | fun add(a, b) {
+(lox) Breakpoint on line 3
+(lox) Breakpoint at line 3 in add
+   3 |   return sum;
+(lox) 30
+(lox) scope 0:
+  a = 1
+  b = 2
+  sum = 3
+globals:
+  add = <fn placehodler>
+  total = 1
+(lox) #0 add at line 3
+#1 <script> at line 7
+(lox) Breakpoint on line 1
+(lox) (lox) line 1
+(lox) Paused at line 9 in <script>
+   9 | print total;
+(lox) 3
+(lox) ";
+        assert_eq!(expected, output);
+
+        // The program's own output is kept apart from the console's
+        assert_eq!("3\n", String::from_utf8(program.0.take()).unwrap());
+    }
+}
@@ -1,145 +1,333 @@
+use crate::ast::Slot;
 use crate::data_types::Object;
 use crate::error::RuntimeError;
-use std::collections::HashMap;
+use crate::gc::{self, Container, Trace, Tracer};
+use crate::heap::{HeapSize, Visited};
+use crate::symbol::{Symbol, SymbolMap};
+use std::cell::RefCell;
+use std::rc::Rc;
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use tracing::instrument;
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct Scope(HashMap<String, Object>);
+/// Variables by name, as the globals and a module's exports are stored.
+pub type Table = SymbolMap<Object>;
+
+impl HeapSize for Table {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.values()
+            .map(|value| std::mem::size_of::<Symbol>() + value.heap_size(visited))
+            .sum()
+    }
+}
+
+impl Trace for Table {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.values().for_each(|value| value.trace(tracer));
+    }
+}
+
+/// The variables of a local scope, in the order they were declared, so
+/// `locals::resolve` can address them by index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scope {
+    names: Vec<Symbol>,
+    values: Vec<Object>,
+}
 
 impl Scope {
     pub fn new() -> Scope {
-        Scope(HashMap::new())
+        Scope::default()
+    }
+
+    // Declaring a name again replaces the value in its slot
+    fn define(&mut self, name: Symbol, value: Object) {
+        match self.position(name) {
+            Some(index) => self.values[index] = value,
+            None => {
+                self.names.push(name);
+                self.values.push(value);
+            }
+        }
+    }
+
+    fn position(&self, name: Symbol) -> Option<usize> {
+        self.names.iter().position(|declared| *declared == name)
+    }
+
+    // `locals::resolve` only hands out slots of names declared before they're
+    // used, so anything else is a bug in it
+    fn slot(&self, index: usize, name: Symbol) -> usize {
+        match self.names.get(index) {
+            Some(declared) if *declared == name => index,
+            declared => panic!(
+                "'{}' resolved to slot {} holding {:?}",
+                name, index, declared
+            ),
+        }
     }
 }
 
-impl std::ops::Deref for Scope {
-    type Target = HashMap<String, Object>;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+impl Trace for Scope {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.values.iter().for_each(|value| value.trace(tracer));
     }
 }
 
-impl std::ops::DerefMut for Scope {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+impl HeapSize for Scope {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.values
+            .iter()
+            .map(|value| std::mem::size_of::<Symbol>() + value.heap_size(visited))
+            .sum()
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Environment {
-    scopes: Vec<Scope>,
+    globals: Rc<RefCell<Table>>,
+    // Local scopes, the innermost last
+    scopes: Vec<Rc<RefCell<Scope>>>,
+}
+
+impl HeapSize for Environment {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        let mut size = 0;
+
+        if visited.first_visit(Rc::as_ptr(&self.globals)) {
+            size += self.globals.borrow().heap_size(visited);
+        }
+        for scope in self.scopes.iter() {
+            if visited.first_visit(Rc::as_ptr(scope)) {
+                size += scope.borrow().heap_size(visited);
+            }
+        }
+
+        size
+    }
+}
+
+impl Trace for Environment {
+    fn trace(&self, tracer: &mut Tracer) {
+        let globals = Container::Table(Rc::downgrade(&self.globals));
+        tracer.visit(&self.globals, Some(globals), |tracer| {
+            self.globals.trace(tracer)
+        });
+        for scope in self.scopes.iter() {
+            let container = Container::Scope(Rc::downgrade(scope));
+            tracer.visit(scope, Some(container), |tracer| scope.trace(tracer));
+        }
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Table::default()));
+        gc::track(Container::Table(Rc::downgrade(&globals)));
+
         Environment {
-            scopes: vec![Scope::new()],
+            globals,
+            scopes: Vec::new(),
         }
     }
 
     pub fn contains(e: &Environment) -> Self {
-        let mut env = Environment {
-            scopes: e.scopes.clone(),
-        };
+        let mut env = e.clone();
         env.new_scope();
         env
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.scopes.is_empty()
+    // Globals are late bound, so the closure shares them with the enclosing
+    // environment (allowing recursion). Enclosing locals are captured by value,
+    // into scopes the closure can reach itself through.
+    pub fn capture(&self) -> Self {
+        let scopes = self
+            .scopes
+            .iter()
+            .map(|scope| {
+                let scope = Rc::new(RefCell::new(scope.borrow().clone()));
+                gc::track(Container::Scope(Rc::downgrade(&scope)));
+                scope
+            })
+            .collect();
+
+        Environment {
+            globals: self.globals.clone(),
+            scopes,
+        }
+    }
+
+    // Bytes retained by the scopes copied by `capture`; the globals are shared
+    pub fn captured_heap_size(&self) -> usize {
+        let mut visited = Visited::new();
+        visited.first_visit(Rc::as_ptr(&self.globals));
+        self.heap_size(&mut visited)
     }
 
     #[instrument(skip(self))]
     pub fn new_scope(&mut self) {
-        self.scopes.push(Scope::new());
+        self.scopes.push(Rc::new(RefCell::new(Scope::new())));
     }
 
-    // TODO: Does this need to be a result?
     #[instrument(skip(self))]
     pub fn end_scope(&mut self) {
-        if self.scopes.len() > 1 {
-            self.scopes.pop();
-        }
+        self.scopes.pop();
     }
 
+    /// Declares `name` in the innermost scope, the globals outside of any.
     #[instrument(skip(self))]
-    pub fn define(&mut self, name: String, value: Object) {
-        match self.scopes.last_mut() {
-            Some(scope) => scope.insert(name, value),
-            None => unreachable!(),
-        };
+    pub fn define(&mut self, name: Symbol, value: Object) {
+        match self.scopes.last() {
+            Some(scope) => scope.borrow_mut().define(name, value),
+            None => {
+                self.globals.borrow_mut().insert(name, value);
+            }
+        }
     }
 
-    pub fn assign(&mut self, name: String, value: Object) -> Result<()> {
-        for scope in self.scopes.iter_mut().rev() {
-            if scope.contains_key(&name) {
-                scope.insert(name, value);
+    // The local scope `depth` scopes out and the index of `name` in it
+    fn local(&self, depth: usize, index: usize, name: Symbol) -> (&RefCell<Scope>, usize) {
+        let scope = self
+            .scopes
+            .len()
+            .checked_sub(depth + 1)
+            .map(|scope| &self.scopes[scope])
+            .unwrap_or_else(|| panic!("'{}' resolved {} scopes out", name, depth));
+        let index = scope.borrow().slot(index, name);
+        (scope, index)
+    }
+
+    pub fn assign(&mut self, name: Symbol, value: Object) -> Result<()> {
+        for scope in self.scopes.iter().rev() {
+            let mut scope = scope.borrow_mut();
+            if let Some(index) = scope.position(name) {
+                scope.values[index] = value;
                 return Ok(());
             }
         }
-        Err(anyhow!("undefined variable '{}'", name))
+        self.assign_global(name, value)
     }
 
-    #[instrument(skip(self))]
-    pub fn assign_at(&mut self, distance: usize, name: String, value: Object) -> Result<()> {
-        let distance = self.scopes.len() - (distance + 1);
-        match self.scopes.get_mut(distance) {
-            Some(scope) => {
-                scope.insert(name, value);
+    /// Assigns the variable resolved to `slot`, or looks `name` up if it
+    /// wasn't resolved.
+    pub fn assign_at(&mut self, slot: Option<Slot>, name: Symbol, value: Object) -> Result<()> {
+        match slot {
+            Some(Slot::Local { depth, index }) => {
+                let (scope, index) = self.local(depth, index, name);
+                scope.borrow_mut().values[index] = value;
                 Ok(())
             }
-            None => Err(anyhow!("exeeded scope depth: {}", distance)),
+            Some(Slot::Global) => self.assign_global(name, value),
+            None => self.assign(name, value),
         }
     }
 
-    #[instrument(skip(self))]
-    pub fn assign_global(&mut self, name: String, value: Object) -> Result<()> {
-        if self.scopes[0].contains_key(&name) {
-            self.scopes[0].insert(name, value);
-            Ok(())
-        } else {
-            Err(anyhow!("undefined variable '{}'", name))
+    fn assign_global(&mut self, name: Symbol, value: Object) -> Result<()> {
+        match self.globals.borrow_mut().get_mut(&name) {
+            Some(global) => {
+                *global = value;
+                Ok(())
+            }
+            None => Err(RuntimeError::UndefinedVariable(name.to_string()).into()),
         }
     }
 
     #[instrument(skip(self))]
-    pub fn get(&self, name: &str) -> Result<Object> {
+    pub fn get(&self, name: Symbol) -> Result<Object> {
         for scope in self.scopes.iter().rev() {
-            if scope.contains_key(name) {
-                return Ok(scope.get(name).unwrap().to_owned());
+            let scope = scope.borrow();
+            if let Some(index) = scope.position(name) {
+                return Ok(scope.values[index].clone());
             }
         }
-        Err(RuntimeError::UndefinedVariable(name.to_string()).into())
+        self.get_global(name)
     }
 
-    pub fn get_global(&self, name: &str) -> Result<Object> {
-        if self.scopes[0].contains_key(name) {
-            Ok(self.scopes[0].get(name).unwrap().to_owned())
-        } else {
-            Err(RuntimeError::UndefinedVariable(name.to_string()).into())
+    fn get_global(&self, name: Symbol) -> Result<Object> {
+        match self.globals.borrow().get(&name) {
+            Some(value) => Ok(value.clone()),
+            None => Err(RuntimeError::UndefinedVariable(name.to_string()).into()),
         }
     }
 
-    pub fn get_at(&self, distance: usize, name: &str) -> Result<Object> {
-        let mut iter = self.scopes.iter().rev();
-
-        for _ in 0..distance {
-            iter.next();
+    /// Reads the variable resolved to `slot`, or looks `name` up if it wasn't
+    /// resolved.
+    pub fn get_at(&self, slot: Option<Slot>, name: Symbol) -> Result<Object> {
+        match slot {
+            Some(Slot::Local { depth, index }) => {
+                let (scope, index) = self.local(depth, index, name);
+                let value = scope.borrow().values[index].clone();
+                Ok(value)
+            }
+            Some(Slot::Global) => self.get_global(name),
+            None => self.get(name),
         }
+    }
 
-        // match self.scopes.get(distance) {
-        match iter.next() {
-            Some(scope) => scope
-                .get(name)
-                .cloned()
-                .ok_or(RuntimeError::UndefinedVariable(name.to_string()).into()),
-            None => Err(anyhow!(
-                "exceeded scope depth: {} > {:?}",
-                distance,
-                self.scopes
-            )),
-        }
+    /// Copies of the variables of each scope, outermost (the globals) first.
+    pub fn scopes(&self) -> Vec<Vec<(String, Object)>> {
+        let globals = self
+            .globals
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        let locals = self.scopes.iter().map(|scope| {
+            let scope = scope.borrow();
+            scope
+                .names
+                .iter()
+                .zip(scope.values.iter())
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect()
+        });
+
+        std::iter::once(globals).chain(locals).collect()
+    }
+
+    pub fn global_names(&self) -> Vec<String> {
+        self.globals
+            .borrow()
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn get_at_slot() {
+        let a = Symbol::intern("a");
+        let mut environment = Environment::new();
+        environment.new_scope();
+        environment.define(a, Object::Number(1.0));
+        environment.new_scope();
+        environment.define(a, Object::Number(2.0));
+
+        // A search by name finds the inner `a`, so reading the outer one
+        // shows the slot was followed
+        let outer = Some(Slot::Local { depth: 1, index: 0 });
+        assert_eq!(Object::Number(1.0), environment.get_at(outer, a).unwrap());
+        assert_eq!(Object::Number(2.0), environment.get_at(None, a).unwrap());
+
+        environment
+            .assign_at(outer, a, Object::Number(3.0))
+            .unwrap();
+        assert_eq!(Object::Number(3.0), environment.get_at(outer, a).unwrap());
+        assert_eq!(Object::Number(2.0), environment.get(a).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "'b' resolved to slot 0")]
+    fn get_at_mismatched_slot() {
+        let mut environment = Environment::new();
+        environment.new_scope();
+        environment.define(Symbol::intern("a"), Object::Number(1.0));
+
+        let slot = Some(Slot::Local { depth: 0, index: 0 });
+        let _ = environment.get_at(slot, Symbol::intern("b"));
     }
 }
@@ -15,6 +15,23 @@ pub enum ParseError {
     UnterminatedString(ErrorLoc),
     #[error("unknown token type")]
     UnknownTokenType,
+    #[error("unexpected character '{0}' on line {1}")]
+    UnexpectedCharacter(char, usize),
+    // Every syntax error in the source, one per line
+    #[error("{}", lines(.0))]
+    Syntax(Vec<SyntaxError>),
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("[line {line}] Error: {message}")]
+pub struct SyntaxError {
+    pub line: usize,
+    pub message: String,
+}
+
+fn lines(errors: &[SyntaxError]) -> String {
+    let lines: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    lines.join("\n")
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -36,10 +53,69 @@ pub enum RuntimeError {
     UndefinedVariable(String),
     #[error("unexpected token: '{0}'")]
     UnexpectedToken(crate::token::Token),
+    #[error("Stack overflow.")]
+    StackOverflow,
+    #[error("Out of memory.")]
+    OutOfMemory,
+    #[error("Only instances have properties.")]
+    NoProperties,
+    #[error("Undefined property '{0}'.")]
+    UndefinedProperty(String),
+    // The thrown value itself is held by the interpreter until it is caught
+    #[error("{0}")]
+    Thrown(String),
+    #[error("Could not import '{0}': {1}.")]
+    Import(String, String),
+    #[error("Import cycle: {0}.")]
+    ImportCycle(String),
+    #[error("Module '{0}' has no export '{1}'.")]
+    UndefinedExport(String, String),
+    #[error("Expected {0} arguments but got {1}.")]
+    Arity(u8, usize),
+    #[error("Argument to '{0}' must be {1}.")]
+    ArgumentType(String, &'static str),
+    #[error("Division by zero.")]
+    DivisionByZero,
+    #[error("Index {0} is out of range for length {1}.")]
+    IndexOutOfRange(i64, usize),
+    #[error("Could not convert '{0}' to a number.")]
+    InvalidNumber(String),
+    #[error("{0}: {1}.")]
+    Io(String, String),
+    #[error("Filesystem access is disabled, so '{0}' can't be used.")]
+    FilesystemDisabled(String),
+    #[error("Assertion failed: {0}.")]
+    AssertionFailed(String),
 }
 
+// Attached as context to runtime errors, recording the line they occurred on
+#[derive(Debug, PartialEq)]
+pub struct Line(pub usize);
+
+// Raised when a script runs out of the resources granted by the host. Kept
+// separate from `RuntimeError` so embedders can tell the two apart.
+#[derive(Error, Debug, PartialEq)]
+pub enum ResourceExhausted {
+    #[error("Step budget exhausted.")]
+    StepBudget,
+    #[error("Deadline exceeded.")]
+    Deadline,
+}
+
+// Raised by the `exit` native to unwind the script, leaving the host to flush
+// output and end the process with the code.
+#[derive(Error, Debug, PartialEq)]
+#[error("Exited with code {0}.")]
+pub struct Exit(pub i32);
+
 impl std::fmt::Display for ErrorLoc {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "line: {}@{}", self.line, self.at)
     }
 }
+
+impl std::fmt::Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[line {}]", self.0)
+    }
+}
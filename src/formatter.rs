@@ -0,0 +1,513 @@
+use crate::ast::{Expr, ExpressionVisitor, Slot, StatementVisitor, Stmt};
+use crate::data_types::{Object, Return};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::token::{Token, TokenType};
+
+use std::borrow::Borrow;
+use std::collections::{HashSet, VecDeque};
+
+use anyhow::Result;
+
+const INDENT: &str = "  ";
+
+/// Re-emits `source` in canonical form: two space indentation, one statement
+/// per line, braces on the line that opens them and at most one blank line
+/// between statements. Comments are kept; formatting the output again leaves
+/// it unchanged.
+pub fn format(source: &str) -> Result<String> {
+    let tokens = Scanner::new(source.to_string())
+        .with_comments()
+        .without_symbols()
+        .scan_tokens()?;
+    let (tokens, trivia) = Trivia::split(tokens, source);
+    let statements = Parser::new(tokens).parse()?;
+
+    let mut formatter = Formatter {
+        out: String::new(),
+        indent: 0,
+        trivia,
+    };
+    formatter.statements(&statements)?;
+    formatter.comments_before(usize::MAX);
+
+    Ok(formatter.out)
+}
+
+#[derive(Debug)]
+struct Comment {
+    line: usize,
+    text: String,
+    // Follows code on the same line, rather than being on a line of its own
+    trailing: bool,
+}
+
+// What the AST doesn't record about the source
+#[derive(Debug)]
+struct Trivia {
+    comments: VecDeque<Comment>,
+    // Line of the closing brace of every `{`, in source order
+    closing: VecDeque<usize>,
+    // Line of every `else`, in source order
+    elses: VecDeque<usize>,
+    blank_lines: HashSet<usize>,
+}
+
+impl Trivia {
+    // Separates the comments out of `tokens`, leaving tokens the parser accepts
+    fn split(tokens: Vec<Token>, source: &str) -> (Vec<Token>, Trivia) {
+        let mut code = Vec::new();
+        let mut comments = VecDeque::new();
+        let mut closing = Vec::new();
+        let mut open = Vec::new();
+        let mut elses = VecDeque::new();
+
+        for token in tokens {
+            match token.token_type {
+                TokenType::Comment(text) => comments.push_back(Comment {
+                    line: token.line,
+                    trailing: code.last().is_some_and(|c: &Token| c.line == token.line),
+                    text,
+                }),
+                _ => {
+                    match token.token_type {
+                        TokenType::LeftBrace => {
+                            open.push(closing.len());
+                            closing.push(token.line);
+                        }
+                        TokenType::RightBrace => {
+                            if let Some(index) = open.pop() {
+                                closing[index] = token.line;
+                            }
+                        }
+                        TokenType::Else => elses.push_back(token.line),
+                        _ => {}
+                    }
+                    code.push(token);
+                }
+            }
+        }
+
+        let blank_lines = source
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.trim().is_empty())
+            .map(|(index, _)| index + 1)
+            .collect();
+
+        let trivia = Trivia {
+            comments,
+            closing: closing.into(),
+            elses,
+            blank_lines,
+        };
+        (code, trivia)
+    }
+}
+
+#[derive(Debug)]
+struct Formatter {
+    out: String,
+    indent: usize,
+    trivia: Trivia,
+}
+
+impl Formatter {
+    fn statements<S: Borrow<Stmt>>(&mut self, statements: &[S]) -> Result<()> {
+        for statement in statements.iter().map(Borrow::borrow) {
+            if let Some(line) = statement.line() {
+                self.comments_before(line);
+                self.separate(line);
+            }
+            self.start_line();
+            statement.accept(self)?;
+            self.out.push('\n');
+        }
+
+        Ok(())
+    }
+
+    // Emits the comments from before `line`. Trailing comments stay at the end
+    // of the last line written.
+    fn comments_before(&mut self, line: usize) {
+        while let Some(comment) = self.trivia.comments.front() {
+            if comment.line >= line {
+                break;
+            }
+            let comment = self.trivia.comments.pop_front().expect("comment");
+
+            if comment.trailing && self.out.ends_with('\n') {
+                self.out.pop();
+                self.out.push_str(&format!(" //{}\n", comment.text));
+            } else {
+                self.separate(comment.line);
+                self.start_line();
+                self.out.push_str(&format!("//{}\n", comment.text));
+            }
+        }
+    }
+
+    // Keeps a blank line from the source before what starts on `line`, unless
+    // it would open a block or the file
+    fn separate(&mut self, line: usize) {
+        if self.trivia.blank_lines.contains(&(line - 1))
+            && !self.out.is_empty()
+            && !self.out.ends_with("{\n")
+            && !self.out.ends_with("\n\n")
+        {
+            self.out.push('\n');
+        }
+    }
+
+    fn start_line(&mut self) {
+        self.out.push_str(&INDENT.repeat(self.indent));
+    }
+
+    // Writes `{`, the statements and `}`, which ends the current line
+    fn block(&mut self, statements: &[Box<Stmt>]) -> Result<()> {
+        let closing = self.trivia.closing.pop_front().unwrap_or(usize::MAX);
+
+        self.out.push_str("{\n");
+        self.indent += 1;
+        self.statements(statements)?;
+        self.comments_before(closing);
+        self.indent -= 1;
+
+        if self.out.ends_with("{\n") {
+            self.out.pop();
+        } else {
+            self.start_line();
+        }
+        self.out.push('}');
+
+        Ok(())
+    }
+
+    // Writes the body of an `if`, `while` or `for` after its header
+    fn body(&mut self, body: &Stmt) -> Result<()> {
+        self.out.push(' ');
+        match body {
+            Stmt::Block(statements) => self.block(statements),
+            _ => body.accept(self).map(|_| ()),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<String> {
+        expr.accept(self)
+    }
+
+    // Formats an expression that may contain blocks into a string
+    fn nested(&mut self, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<String> {
+        let outer = std::mem::take(&mut self.out);
+        let result = f(self);
+        let nested = std::mem::replace(&mut self.out, outer);
+        result.map(|_| nested)
+    }
+}
+
+// Comma separated names, e.g. parameters
+fn list(tokens: &[Token]) -> String {
+    let names: Vec<String> = tokens.iter().map(Token::to_string).collect();
+    names.join(", ")
+}
+
+impl StatementVisitor for Formatter {
+    fn visit_block(&mut self, statements: &[Box<Stmt>]) -> Result<Return> {
+        self.block(statements)?;
+        Ok(Return::None)
+    }
+
+    fn visit_if(
+        &mut self,
+        _token: &Token,
+        condition: &Expr,
+        then: &Stmt,
+        els: Option<&Stmt>,
+    ) -> Result<Return> {
+        let condition = self.expr(condition)?;
+        self.out.push_str(&format!("if ({})", condition));
+        self.body(then)?;
+
+        if let Some(els) = els {
+            // Comments after the `then` branch belong before the `else`
+            let line = self.trivia.elses.pop_front().unwrap_or(usize::MAX);
+            self.out.push('\n');
+            let written = self.out.len();
+            self.comments_before(line);
+
+            match then {
+                Stmt::Block(_) if self.out.len() == written => {
+                    self.out.pop();
+                    self.out.push(' ');
+                }
+                _ => self.start_line(),
+            }
+            self.out.push_str("else");
+            self.body(els)?;
+        }
+
+        Ok(Return::None)
+    }
+
+    fn visit_print(&mut self, expr: &Expr) -> Result<Return> {
+        let expr = self.expr(expr)?;
+        self.out.push_str(&format!("print {};", expr));
+        Ok(Return::None)
+    }
+
+    fn visit_expression(&mut self, expr: &Expr) -> Result<Return> {
+        let expr = self.expr(expr)?;
+        self.out.push_str(&format!("{};", expr));
+        Ok(Return::None)
+    }
+
+    fn visit_variable(&mut self, name: &Token, initializer: Option<&Expr>) -> Result<Return> {
+        match initializer {
+            Some(initializer) => {
+                let initializer = self.expr(initializer)?;
+                self.out
+                    .push_str(&format!("var {} = {};", name, initializer));
+            }
+            None => self.out.push_str(&format!("var {};", name)),
+        }
+        Ok(Return::None)
+    }
+
+    fn visit_while(&mut self, _token: &Token, condition: &Expr, body: &Stmt) -> Result<Return> {
+        let condition = self.expr(condition)?;
+        self.out.push_str(&format!("while ({})", condition));
+        self.body(body)?;
+        Ok(Return::None)
+    }
+
+    fn visit_for(
+        &mut self,
+        _token: &Token,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Result<Return> {
+        self.out.push_str("for (");
+        match initializer {
+            Some(initializer) => {
+                initializer.accept(&mut *self)?;
+            }
+            None => self.out.push(';'),
+        }
+        if let Some(condition) = condition {
+            let condition = self.expr(condition)?;
+            self.out.push_str(&format!(" {}", condition));
+        }
+        self.out.push(';');
+        if let Some(increment) = increment {
+            let increment = self.expr(increment)?;
+            self.out.push_str(&format!(" {}", increment));
+        }
+        self.out.push(')');
+        self.body(body)?;
+        Ok(Return::None)
+    }
+
+    fn visit_function(
+        &mut self,
+        name: &Token,
+        params: &[Token],
+        body: &[Box<Stmt>],
+    ) -> Result<Return> {
+        self.out
+            .push_str(&format!("fun {}({}) ", name, list(params)));
+        self.block(body)?;
+        Ok(Return::None)
+    }
+
+    fn visit_return(&mut self, _token: &Token, expr: Option<&Expr>) -> Result<Return> {
+        match expr {
+            Some(expr) => {
+                let expr = self.expr(expr)?;
+                self.out.push_str(&format!("return {};", expr));
+            }
+            None => self.out.push_str("return;"),
+        }
+        Ok(Return::None)
+    }
+
+    fn visit_throw(&mut self, _token: &Token, expr: &Expr) -> Result<Return> {
+        let expr = self.expr(expr)?;
+        self.out.push_str(&format!("throw {};", expr));
+        Ok(Return::None)
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &[Box<Stmt>],
+        catch: Option<(&Token, &[Box<Stmt>])>,
+        finally: Option<&[Box<Stmt>]>,
+    ) -> Result<Return> {
+        self.out.push_str("try ");
+        self.block(body)?;
+        if let Some((name, handler)) = catch {
+            self.out.push_str(&format!(" catch ({}) ", name));
+            self.block(handler)?;
+        }
+        if let Some(finally) = finally {
+            self.out.push_str(" finally ");
+            self.block(finally)?;
+        }
+        Ok(Return::None)
+    }
+
+    fn visit_import(
+        &mut self,
+        _token: &Token,
+        path: &str,
+        names: Option<&[Token]>,
+    ) -> Result<Return> {
+        match names {
+            Some(names) => {
+                self.out
+                    .push_str(&format!("import {{ {} }} from \"{}\";", list(names), path))
+            }
+            None => self.out.push_str(&format!("import \"{}\";", path)),
+        }
+        Ok(Return::None)
+    }
+}
+
+impl ExpressionVisitor<String> for Formatter {
+    fn visit_assignment(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _slot: Option<Slot>,
+    ) -> Result<String> {
+        Ok(format!("{} = {}", name, self.expr(value)?))
+    }
+
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<String> {
+        let left = self.expr(left)?;
+        Ok(format!("{} {} {}", left, operator, self.expr(right)?))
+    }
+
+    fn visit_call(&mut self, callee: &Expr, _paren: &Token, args: &[Box<Expr>]) -> Result<String> {
+        let callee = self.expr(callee)?;
+        let args = args
+            .iter()
+            .map(|arg| self.expr(arg))
+            .collect::<Result<Vec<String>>>()?;
+        Ok(format!("{}({})", callee, args.join(", ")))
+    }
+
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> Result<String> {
+        Ok(format!("{}.{}", self.expr(object)?, name))
+    }
+
+    fn visit_grouping(&mut self, grouping: &Expr) -> Result<String> {
+        Ok(format!("({})", self.expr(grouping)?))
+    }
+
+    fn visit_lambda(
+        &mut self,
+        token: &Token,
+        params: &[Token],
+        body: &[Box<Stmt>],
+    ) -> Result<String> {
+        let arrow = token.token_type == TokenType::LeftParen;
+
+        // An arrow function with an expression body is a lone `return`
+        // holding the arrow's token
+        if let [body] = body {
+            if let Stmt::Return {
+                token,
+                value: Some(value),
+            } = &**body
+            {
+                if token.token_type == TokenType::Arrow {
+                    return Ok(format!("({}) => {}", list(params), self.expr(value)?));
+                }
+            }
+        }
+
+        let body = self.nested(|formatter| formatter.block(body))?;
+        match arrow {
+            true => Ok(format!("({}) => {}", list(params), body)),
+            false => Ok(format!("fun ({}) {}", list(params), body)),
+        }
+    }
+
+    fn visit_unary(&mut self, operator: &Token, right: &Expr) -> Result<String> {
+        Ok(format!("{}{}", operator, self.expr(right)?))
+    }
+
+    fn visit_literal(&mut self, literal: &Object) -> Result<String> {
+        match literal {
+            Object::String(s) => Ok(format!("\"{}\"", s)),
+            literal => Ok(literal.to_string()),
+        }
+    }
+
+    fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<String> {
+        let left = self.expr(left)?;
+        Ok(format!("{} {} {}", left, operator, self.expr(right)?))
+    }
+
+    fn visit_variable(&mut self, name: &Token, _slot: Option<Slot>) -> Result<String> {
+        Ok(name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::format;
+
+    #[test]
+    fn canonical() {
+        let source = "// Counts\nvar  a=1 ;\n\n\n\nfun f(x,y){ // add\nreturn x+y;\n\n// done\n}\nfor(var i=0;i<2;i=i+1) print f(i,a);\nif (a) { print -a; } else print !a;\nvar g = (x) => x*2;\nwhile(false){}\n";
+        let expected = "// Counts
+var a = 1;
+
+fun f(x, y) { // add
+  return x + y;
+
+  // done
+}
+for (var i = 0; i < 2; i = i + 1) print f(i, a);
+if (a) {
+  print -a;
+} else print !a;
+var g = (x) => x * 2;
+while (false) {}
+";
+
+        let formatted = format(source).unwrap();
+        assert_eq!(expected, formatted);
+        assert_eq!(formatted, format(&formatted).unwrap());
+    }
+
+    #[test]
+    fn lambda_blocks() {
+        let source = "var f = fun (a) {\nreturn fun () { return a; };\n};\nf(1);\n";
+        let expected = "var f = fun (a) {\n  return fun () {\n    return a;\n  };\n};\nf(1);\n";
+
+        assert_eq!(expected, format(source).unwrap());
+    }
+
+    #[test]
+    fn comments_before_else() {
+        let source = "if (a) print a; // t1\nelse { print 2; }\nif (a) { print 1; } // t2\n// t3\nelse print 2; // t4\n";
+        let expected = "if (a) print a; // t1
+else {
+  print 2;
+}
+if (a) {
+  print 1;
+} // t2
+// t3
+else print 2; // t4
+";
+
+        let formatted = format(source).unwrap();
+        assert_eq!(expected, formatted);
+        assert_eq!(formatted, format(&formatted).unwrap());
+    }
+}
@@ -0,0 +1,356 @@
+// A cycle collector for the values reference counting can't free. Closures
+// share the globals and own the locals they capture, so a function stored in a
+// scope it closes over keeps itself alive: every global function once its
+// interpreter is dropped, or a closure assigned to one of its captured
+// variables.
+//
+// The scopes that can close a cycle are registered as they're created. A
+// collection walks everything reachable from them, counting the references
+// each value gets from the others. A value referenced more often than that is
+// also held from outside, by the interpreter's stacks or a native mid-call,
+// and is a root. Whatever the roots don't reach is only kept alive by cycles,
+// which emptying its scopes breaks.
+
+use crate::environment::{Scope, Table};
+use crate::heap::{HeapSize, Visited};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// Scopes registered between automatic collections.
+pub const DEFAULT_THRESHOLD: usize = 10_000;
+
+// Registered scopes are pruned of the dropped ones once there are this many
+const MIN_PRUNE: usize = 1024;
+
+thread_local! {
+    // Reference counting isn't thread safe, so each thread has its own heap
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+}
+
+#[derive(Default)]
+struct Heap {
+    containers: Vec<Container>,
+    // Length of `containers` that triggers the next pruning
+    prune_at: usize,
+    // Containers registered since the last collection
+    allocated: usize,
+}
+
+impl Heap {
+    fn prune(&mut self) {
+        self.containers.retain(Container::is_alive);
+        self.prune_at = (self.containers.len() * 2).max(MIN_PRUNE);
+    }
+}
+
+/// A mutable value that can close a cycle, emptied if found unreachable.
+#[derive(Clone)]
+pub enum Container {
+    Scope(Weak<RefCell<Scope>>),
+    Table(Weak<RefCell<Table>>),
+}
+
+impl Container {
+    fn is_alive(&self) -> bool {
+        match self {
+            Container::Scope(scope) => scope.strong_count() > 0,
+            Container::Table(table) => table.strong_count() > 0,
+        }
+    }
+
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Container::Scope(scope) => {
+                if let Some(scope) = scope.upgrade() {
+                    tracer.visit(&scope, Some(self.clone()), |tracer| scope.trace(tracer));
+                }
+            }
+            Container::Table(table) => {
+                if let Some(table) = table.upgrade() {
+                    tracer.visit(&table, Some(self.clone()), |tracer| table.trace(tracer));
+                }
+            }
+        }
+    }
+
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        match self {
+            Container::Scope(scope) => scope
+                .upgrade()
+                .map_or(0, |scope| scope.borrow().heap_size(visited)),
+            Container::Table(table) => table
+                .upgrade()
+                .map_or(0, |table| table.borrow().heap_size(visited)),
+        }
+    }
+
+    // The contents are dropped once the borrow is released, as dropping them
+    // can free other containers
+    fn clear(&self) {
+        match self {
+            Container::Scope(scope) => {
+                if let Some(scope) = scope.upgrade() {
+                    let contents = std::mem::take(&mut *scope.borrow_mut());
+                    drop(contents);
+                }
+            }
+            Container::Table(table) => {
+                if let Some(table) = table.upgrade() {
+                    let contents = std::mem::take(&mut *table.borrow_mut());
+                    drop(contents);
+                }
+            }
+        }
+    }
+}
+
+/// Registers a container that could be part of a cycle.
+pub fn track(container: Container) {
+    HEAP.with_borrow_mut(|heap| {
+        heap.containers.push(container);
+        heap.allocated += 1;
+        if heap.containers.len() >= heap.prune_at {
+            heap.prune();
+        }
+    });
+}
+
+/// Containers registered since the last collection.
+pub fn allocated() -> usize {
+    HEAP.with_borrow(|heap| heap.allocated)
+}
+
+/// What a collection freed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Collection {
+    /// Scopes emptied to break cycles
+    pub containers: usize,
+    /// Bytes retained by the values freed, see `HeapSize`
+    pub freed: usize,
+}
+
+/// Totals over the collections an interpreter ran.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub collections: u64,
+    pub containers: usize,
+    pub freed: usize,
+}
+
+impl Stats {
+    pub fn record(&mut self, collection: Collection) {
+        self.collections += 1;
+        self.containers += collection.containers;
+        self.freed += collection.freed;
+    }
+}
+
+/// Frees the values of this thread only kept alive by reference cycles.
+pub fn collect() -> Collection {
+    let containers = HEAP.with_borrow_mut(|heap| {
+        heap.prune();
+        heap.allocated = 0;
+        heap.containers.clone()
+    });
+
+    let mut tracer = Tracer::default();
+    for container in containers.iter() {
+        container.trace(&mut tracer);
+    }
+    tracer.sweep()
+}
+
+/// Reports the reference counted values a value holds to the collector.
+pub trait Trace {
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+// Contents that are borrowed for writing can't be traced, so the value is
+// kept along with everything it holds
+impl<T: Trace> Trace for RefCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self.try_borrow() {
+            Ok(value) => value.trace(tracer),
+            Err(_) => tracer.pin(),
+        }
+    }
+}
+
+/// The graph of reference counted values reachable from the containers.
+#[derive(Default)]
+pub struct Tracer {
+    index: HashMap<*const (), usize>,
+    nodes: Vec<Node>,
+    // Node whose contents are being traced
+    parent: Option<usize>,
+}
+
+struct Node {
+    address: *const (),
+    // Strong references to the value, and those held by other nodes
+    references: usize,
+    internal: usize,
+    children: Vec<usize>,
+    container: Option<Container>,
+    // Its contents couldn't be traced
+    pinned: bool,
+}
+
+impl Tracer {
+    /// Records a reference to `value` from the value being traced, tracing
+    /// what it holds with `contents` the first time it's seen.
+    pub fn visit<T: ?Sized>(
+        &mut self,
+        value: &Rc<T>,
+        container: Option<Container>,
+        contents: impl FnOnce(&mut Tracer),
+    ) {
+        let address = Rc::as_ptr(value) as *const ();
+        if let Some(&index) = self.index.get(&address) {
+            self.reference(index);
+            return;
+        }
+
+        let index = self.nodes.len();
+        self.index.insert(address, index);
+        self.nodes.push(Node {
+            address,
+            // Containers are traced through an upgrade, which isn't a reference
+            // held by the program
+            references: Rc::strong_count(value) - usize::from(self.parent.is_none()),
+            internal: 0,
+            children: Vec::new(),
+            container,
+            pinned: false,
+        });
+        self.reference(index);
+
+        let parent = self.parent.replace(index);
+        contents(self);
+        self.parent = parent;
+    }
+
+    /// Keeps the value being traced, whose contents can't be read.
+    pub fn pin(&mut self) {
+        if let Some(parent) = self.parent {
+            self.nodes[parent].pinned = true;
+        }
+    }
+
+    fn reference(&mut self, index: usize) {
+        if let Some(parent) = self.parent {
+            self.nodes[parent].children.push(index);
+            self.nodes[index].internal += 1;
+        }
+    }
+
+    fn sweep(self) -> Collection {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut pending: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| self.nodes[i].pinned || self.nodes[i].references > self.nodes[i].internal)
+            .collect();
+        while let Some(index) = pending.pop() {
+            if !std::mem::replace(&mut reachable[index], true) {
+                pending.extend(&self.nodes[index].children);
+            }
+        }
+
+        // Live values the garbage refers to aren't freed with it
+        let mut visited = Visited::new();
+        let mut garbage = Vec::new();
+        for (node, reachable) in self.nodes.into_iter().zip(reachable) {
+            if reachable {
+                visited.first_visit(node.address);
+            } else if let Some(container) = node.container {
+                garbage.push(container);
+            }
+        }
+
+        let freed = garbage
+            .iter()
+            .map(|container| container.heap_size(&mut visited))
+            .sum();
+        for container in garbage.iter() {
+            container.clear();
+        }
+
+        Collection {
+            containers: garbage.len(),
+            freed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Collection;
+    use crate::data_types::Object;
+    use crate::interpreter::Interpreter;
+
+    fn run(interpreter: &mut Interpreter, source: &str) {
+        let tokens = crate::scanner::Scanner::new(source.to_string())
+            .scan_tokens()
+            .unwrap();
+        let statements = crate::parser::Parser::new(tokens).parse().unwrap();
+        interpreter.interpret(statements).unwrap();
+    }
+
+    #[test]
+    fn collects_cycles_only() {
+        let mut interpreter = Interpreter::new().without_gc();
+        run(
+            &mut interpreter,
+            "\
+var keep;
+fun leak() {
+  var me;
+  var f = fun() { me = keep; };
+  keep = f;
+  f();
+  keep = nil;
+}
+for (var i = 0; i < 10; i = i + 1) leak();
+
+var calls = 0;
+fun count() { calls = calls + 1; return calls; }
+",
+        );
+
+        // Each closure holds itself through its captured `me`
+        let collection = interpreter.collect_garbage();
+        assert_eq!(10, collection.containers);
+        assert!(collection.freed > 0);
+        assert_eq!(Collection::default(), interpreter.collect_garbage());
+
+        // The globals and `count` refer to each other, but are still in use
+        run(&mut interpreter, "count(); var freed = gc(); count();");
+        assert_eq!(Object::Number(2.0), interpreter.variable("calls").unwrap());
+        assert_eq!(3, interpreter.gc_stats().collections);
+
+        // Dropping the interpreter leaves the globals' cycle behind
+        drop(interpreter);
+        assert_eq!(1, super::collect().containers);
+    }
+
+    #[test]
+    fn collects_automatically() {
+        let mut interpreter = Interpreter::new().with_gc_threshold(5);
+        run(
+            &mut interpreter,
+            "\
+var keep;
+for (var i = 0; i < 20; i = i + 1) {
+  var me;
+  keep = fun() { me = keep; };
+  keep();
+}
+",
+        );
+
+        let stats = interpreter.gc_stats();
+        assert!(stats.collections >= 3);
+        assert!(stats.containers > 0);
+    }
+}
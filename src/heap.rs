@@ -0,0 +1,23 @@
+use std::collections::HashSet;
+
+/// Records the heap values already counted during a measurement, so values
+/// shared between environments (e.g. the globals every closure captures) are
+/// only counted once.
+#[derive(Debug, Default)]
+pub struct Visited(HashSet<*const ()>);
+
+impl Visited {
+    pub fn new() -> Self {
+        Visited(HashSet::new())
+    }
+
+    pub fn first_visit<T: ?Sized>(&mut self, value: *const T) -> bool {
+        self.0.insert(value as *const ())
+    }
+}
+
+/// Approximate number of bytes retained by a value, including everything
+/// reachable from it that hasn't been visited yet.
+pub trait HeapSize {
+    fn heap_size(&self, visited: &mut Visited) -> usize;
+}
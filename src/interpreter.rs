@@ -1,50 +1,378 @@
-use crate::ast::{Expr, ExpressionVisitor, StatementVisitor, Stmt};
-use crate::data_types::{Clock, Function, Object, Return};
-use crate::environment::Environment;
-use crate::error::{EvaluationError, RuntimeError};
+use crate::ast::{Expr, ExpressionVisitor, Slot, StatementVisitor, Stmt};
+use crate::coverage::{Coverage, UNNAMED};
+use crate::data_types::{Clock, ErrorValue, Function, Object, Return};
+use crate::debugger::Debugger;
+use crate::environment::{Environment, Table};
+use crate::error::{EvaluationError, Exit, Line, ResourceExhausted, RuntimeError};
+use crate::gc::{self, Collection};
+use crate::heap::{HeapSize, Visited};
+use crate::locals;
+use crate::module::Modules;
+use crate::natives;
+use crate::optimizer;
+use crate::parser::Parser;
+use crate::profiler::Profiler;
+use crate::scanner::Scanner;
+use crate::symbol::Symbol;
 use crate::token::{Token, TokenType};
 
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
 use anyhow::{anyhow, Result};
-use tracing::instrument;
+use tracing::{instrument, trace};
+
+/// Target of the execution trace events, see `lox --trace`.
+pub const TRACE_TARGET: &str = "lox::trace";
+
+/// Maximum number of nested Lox function calls before raising `Stack overflow.`
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1024;
+
+// Stack left when a call raises `Stack overflow.` instead of running, enough
+// for the Rust frames of several more calls in a debug build (~20KB each), so
+// the limit holds on threads too small for `max_call_depth` calls
+const STACK_RED_ZONE: usize = 256 * 1024;
+
+// Reading the clock on every step is comparatively expensive, so the deadline
+// is only checked once per this many steps.
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+// Where `print` writes
+struct Output(Box<dyn Write>);
+
+impl std::fmt::Debug for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Output")
+    }
+}
 
 #[derive(Debug)]
 pub struct Interpreter {
     environment: Environment,
+    // Environments of the callers of the currently executing function
+    frames: Vec<Environment>,
+    max_call_depth: usize,
+    steps: u64,
+    step_budget: Option<u64>,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    max_heap: Option<usize>,
+    // Heap usage as of the last measurement, and bytes allocated since then
+    heap_measured: usize,
+    heap_allocated: usize,
+    // Scopes registered before cycles are collected, if they are automatically
+    gc_threshold: Option<usize>,
+    gc_stats: gc::Stats,
+    // Value of the `throw` currently unwinding, see `RuntimeError::Thrown`
+    thrown: Option<Object>,
+    modules: Modules,
+    // Script or module whose code is executing, if it was read from a file
+    file: Option<Rc<Path>>,
+    filesystem: bool,
+    // The script's command line arguments, bound to `ARGS`
+    arguments: Object,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    debugger: Option<Box<dyn Debugger>>,
+    output: Output,
+    // Whether to fold constants before running code, see `optimizer`
+    optimize: bool,
+}
+
+// Path of the executing file for coverage
+fn file(file: &Option<Rc<Path>>) -> &Path {
+    file.as_deref().unwrap_or(Path::new(UNNAMED))
+}
+
+// A fresh global scope holding the natives and `ARGS`; each module gets its own
+fn globals(arguments: &Object) -> Environment {
+    let mut globals = Environment::new();
+    globals.define("ARGS".into(), arguments.clone());
+    globals.define("clock".into(), Object::Function(std::rc::Rc::new(Clock)));
+    globals.define("math".into(), natives::math::math());
+    natives::string::globals(&mut globals);
+    natives::convert::globals(&mut globals);
+    natives::io::globals(&mut globals);
+    natives::process::globals(&mut globals);
+    natives::assert::globals(&mut globals);
+    natives::gc::globals(&mut globals);
+    globals
 }
 
 impl Interpreter {
+    /// Calls raise `Stack overflow.` past `DEFAULT_MAX_CALL_DEPTH`, or once
+    /// the thread's stack is nearly exhausted. Reaching the default depth takes
+    /// about 5MB of stack in a release build and 24MB in a debug build.
     pub fn new() -> Self {
-        let mut globals = Environment::new();
-        globals.define(
-            "clock".to_string(),
-            Object::Function(std::rc::Rc::new(Clock)),
-        );
+        let arguments = Object::List(Rc::new(Vec::new()));
+
         Interpreter {
-            environment: globals,
+            environment: globals(&arguments),
+            frames: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            steps: 0,
+            step_budget: None,
+            timeout: None,
+            deadline: None,
+            max_heap: None,
+            heap_measured: 0,
+            heap_allocated: 0,
+            gc_threshold: Some(gc::DEFAULT_THRESHOLD),
+            gc_stats: gc::Stats::default(),
+            thrown: None,
+            modules: Modules::new(),
+            file: None,
+            filesystem: true,
+            arguments,
+            profiler: None,
+            coverage: None,
+            debugger: None,
+            output: Output(Box::new(std::io::stdout())),
+            optimize: false,
         }
     }
 
-    // #[instrument(skip(self), ret, level = "trace")]
-    // pub fn resolve(&mut self, name: Expr, depth: usize) {
-    // dbg!(&name);
-    // println!("Resolve: {}: {}", &name, self.locals.len());
-    // self.locals.insert(name.clone(), depth);
-    // println!("Resolve: {}: {}", &name, self.locals.len());
-    // }
+    /// Binds the script's command line arguments to `ARGS`.
+    pub fn with_arguments(mut self, arguments: Vec<String>) -> Self {
+        let arguments = arguments
+            .into_iter()
+            .map(|argument| Object::String(argument.into()))
+            .collect();
+        self.arguments = Object::List(Rc::new(arguments));
+        self.environment
+            .define("ARGS".into(), self.arguments.clone());
+        self
+    }
 
-    // pub fn look_up_variable(&self, name: Token) -> Result<Object> {
-    //     let lexeme = name.lexeme.clone().unwrap();
-    //     let name = Expr::Variable(name);
-    //
-    //
-    //     match self.locals.get(&name) {
-    //         Some(distance) => self.environment.get_at(*distance, &lexeme),
-    //         None => self.environment.get_global(&lexeme),
-    //     }
-    // }
+    /// Records the time spent in each Lox function, see `profiler`.
+    pub fn with_profiler(mut self) -> Self {
+        self.profiler = Some(Profiler::new());
+        self
+    }
+
+    /// Counts the executions of each statement and branch, see `coverage`.
+    pub fn with_coverage(mut self) -> Self {
+        self.coverage = Some(Coverage::new());
+        self
+    }
+
+    /// Sends the output of `print` to `output` instead of stdout.
+    pub fn with_output(mut self, output: impl Write + 'static) -> Self {
+        self.output = Output(Box::new(output));
+        self
+    }
+
+    /// Folds constants and removes dead branches of the script and its
+    /// modules before running them.
+    pub fn with_optimizer(mut self) -> Self {
+        self.optimize = true;
+        self
+    }
+
+    /// Lets `debugger` pause execution before each statement.
+    pub fn with_debugger(mut self, debugger: impl Debugger + 'static) -> Self {
+        self.debugger = Some(Box::new(debugger));
+        self
+    }
+
+    /// Makes the file natives and `import` raise an error instead of touching
+    /// the filesystem.
+    pub fn without_filesystem(mut self) -> Self {
+        self.filesystem = false;
+        self
+    }
+
+    /// Resolves imports relative to the script at `path`.
+    pub fn with_script(mut self, path: &Path) -> Self {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.file = Some(path.as_path().into());
+        self.modules.set_root(path);
+        self
+    }
+
+    /// Limits the depth of nested Lox calls. Running out of stack first raises
+    /// `Stack overflow.` at a shallower depth, see `Interpreter::new`.
+    pub fn with_max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.max_call_depth = max_call_depth;
+        self
+    }
+
+    /// Limits the total number of statements and expressions evaluated.
+    pub fn with_step_budget(mut self, steps: u64) -> Self {
+        self.step_budget = Some(steps);
+        self
+    }
+
+    /// Limits the wall-clock time of each call to `interpret`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Limits the approximate number of bytes retained by Lox values.
+    pub fn with_max_heap(mut self, bytes: usize) -> Self {
+        self.max_heap = Some(bytes);
+        self
+    }
+
+    /// Collects reference cycles once this many scopes that could be part of
+    /// one have been created since the last collection.
+    pub fn with_gc_threshold(mut self, scopes: usize) -> Self {
+        self.gc_threshold = Some(scopes);
+        self
+    }
+
+    /// Leaves collecting reference cycles to `gc()` and the embedder.
+    pub fn without_gc(mut self) -> Self {
+        self.gc_threshold = None;
+        self
+    }
+
+    /// Looks up a variable visible from the current scope.
+    pub fn variable(&self, name: &str) -> Result<Object> {
+        self.environment.get(name.into())
+    }
+
+    /// Evaluates `expression` in the current scope.
+    pub fn evaluate(&mut self, expression: &Expr) -> Result<Object> {
+        evaluate(self, expression)
+    }
+
+    /// Scopes of the environment `frame` calls up the stack, where 0 is the
+    /// executing function, outermost (the globals) first.
+    pub fn scopes(&self, frame: usize) -> Option<Vec<Vec<(String, Object)>>> {
+        let environment = match frame {
+            0 => &self.environment,
+            _ => self
+                .frames
+                .len()
+                .checked_sub(frame)
+                .map(|i| &self.frames[i])?,
+        };
+        Some(environment.scopes())
+    }
+
+    /// Names of the globals, including the natives.
+    pub fn global_names(&self) -> Vec<String> {
+        self.environment.global_names()
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    pub fn filesystem(&self) -> bool {
+        self.filesystem
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Approximate number of bytes retained by values reachable from the
+    /// current environment, those of its callers and imported modules.
+    pub fn heap_usage(&self) -> usize {
+        let mut visited = Visited::new();
+
+        std::iter::once(&self.environment)
+            .chain(self.frames.iter())
+            .map(|environment| environment.heap_size(&mut visited))
+            .sum::<usize>()
+            + self.modules.heap_size(&mut visited)
+    }
+
+    /// Accounts for a new allocation, raising `Out of memory.` if it would
+    /// exceed the heap limit. The heap is only measured when the estimate (last
+    /// measurement plus everything allocated since) could exceed the limit,
+    /// since measuring walks every reachable value.
+    pub fn allocate(&mut self, bytes: usize) -> Result<()> {
+        let Some(max_heap) = self.max_heap else {
+            return Ok(());
+        };
+
+        self.heap_allocated += bytes;
+        if self.heap_measured + self.heap_allocated > max_heap {
+            self.heap_measured = self.heap_usage();
+            self.heap_allocated = bytes;
+
+            if self.heap_measured + bytes > max_heap {
+                return Err(RuntimeError::OutOfMemory.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Frees the values only kept alive by reference cycles, see `gc`.
+    pub fn collect_garbage(&mut self) -> Collection {
+        let collection = gc::collect();
+        self.gc_stats.record(collection);
+        collection
+    }
+
+    pub fn gc_stats(&self) -> gc::Stats {
+        self.gc_stats
+    }
+
+    // Called before capturing a closure, when no scope is borrowed
+    fn collect_if_due(&mut self) {
+        if self
+            .gc_threshold
+            .is_some_and(|threshold| gc::allocated() >= threshold)
+        {
+            self.collect_garbage();
+        }
+    }
+
+    fn tick(&mut self) -> Result<()> {
+        self.steps += 1;
+
+        if let Some(budget) = self.step_budget {
+            if self.steps > budget {
+                return Err(ResourceExhausted::StepBudget.into());
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            if self.steps.is_multiple_of(DEADLINE_CHECK_INTERVAL) && Instant::now() >= deadline {
+                return Err(ResourceExhausted::Deadline.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn cover_branch(&mut self, token: &Token, entered: bool) {
+        if let Some(coverage) = &mut self.coverage {
+            coverage.branch(file(&self.file), token, entered);
+        }
+    }
+
+    // Converts a caught error into the value bound by a `catch` clause
+    fn caught(&mut self, error: anyhow::Error) -> Object {
+        match error.downcast_ref::<RuntimeError>() {
+            Some(RuntimeError::Thrown(_)) => self.thrown.take().unwrap_or(Object::Nil),
+            _ => Object::Error(Rc::new(ErrorValue {
+                message: error.root_cause().to_string(),
+                line: error.downcast_ref::<Line>().map(|line| line.0),
+            })),
+        }
+    }
 
     #[instrument(skip(self), ret, level = "trace")]
     pub fn interpret(&mut self, statements: Vec<Stmt>) -> Result<()> {
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let statements = self.prepare(statements);
+        if let Some(coverage) = &mut self.coverage {
+            coverage.register(file(&self.file), &statements);
+        }
+
         for statement in statements {
             execute(self, &statement)?;
         }
@@ -52,15 +380,116 @@ impl Interpreter {
         Ok(())
     }
 
-    #[instrument(skip(self), ret, level = "trace")]
-    pub fn with_environment(&self, e: Environment) -> Interpreter {
-        Interpreter { environment: e }
+    // Optimizes the statements if enabled, and resolves their locals
+    fn prepare(&self, statements: Vec<Stmt>) -> Vec<Stmt> {
+        let mut statements = match self.optimize {
+            true => optimizer::optimize(statements),
+            false => statements,
+        };
+        locals::resolve(&mut statements);
+        statements
+    }
+
+    // Runs a function body in its own environment, restoring the caller's
+    // environment afterwards regardless of how the body exits.
+    #[instrument(skip(self, environment), ret, level = "trace")]
+    pub fn execute_function(
+        &mut self,
+        name: &str,
+        file: Option<Rc<Path>>,
+        environment: Environment,
+        body: &[Box<Stmt>],
+    ) -> Result<Return> {
+        let stack_exhausted = stacker::remaining_stack().is_some_and(|left| left < STACK_RED_ZONE);
+        if self.frames.len() >= self.max_call_depth || stack_exhausted {
+            return Err(RuntimeError::StackOverflow.into());
+        }
+
+        let previous = std::mem::replace(&mut self.environment, environment);
+        self.frames.push(previous);
+        let caller = std::mem::replace(&mut self.file, file);
+        if let Some(debugger) = &mut self.debugger {
+            debugger.enter(name);
+        }
+
+        let result = self.execute_block(body);
+
+        if let Some(debugger) = &mut self.debugger {
+            debugger.exit();
+        }
+        self.file = caller;
+        self.environment = self.frames.pop().expect("caller environment");
+
+        result
+    }
+
+    // Runs the module at `path` (relative to the importing file) the first time
+    // it's imported, and returns its top-level definitions.
+    fn import(&mut self, path: &str) -> Result<Rc<Table>> {
+        if !self.filesystem {
+            return Err(RuntimeError::FilesystemDisabled("import".to_string()).into());
+        }
+
+        // The file whose code is running wrote the `import`, even when it's
+        // in a function called from another file
+        let path = Modules::resolve(self.file.as_deref(), path)?;
+        if let Some(exports) = self.modules.get(&path) {
+            return Ok(exports);
+        }
+
+        self.modules.enter(path.clone())?;
+        let result = self.execute_module(&path);
+        self.modules.exit();
+
+        Ok(self.modules.insert(path, result?))
+    }
+
+    fn execute_module(&mut self, path: &Path) -> Result<Table> {
+        let source = std::fs::read_to_string(path)?;
+        let tokens = Scanner::new(source).scan_tokens()?;
+        let statements = self.prepare(Parser::new(tokens).parse()?);
+        if let Some(coverage) = &mut self.coverage {
+            coverage.register(path, &statements);
+        }
+
+        let previous = std::mem::replace(&mut self.environment, globals(&self.arguments));
+        self.frames.push(previous);
+        let importer = self.file.replace(path.into());
+        if let Some(debugger) = &mut self.debugger {
+            debugger.enter(&path.display().to_string());
+        }
+
+        let result = statements
+            .iter()
+            .try_for_each(|statement| execute(self, statement).map(drop));
+
+        if let Some(debugger) = &mut self.debugger {
+            debugger.exit();
+        }
+        self.file = importer;
+
+        let environment = std::mem::replace(
+            &mut self.environment,
+            self.frames.pop().expect("importer environment"),
+        );
+        result?;
+
+        let mut exports = Table::default();
+        for statement in statements.iter() {
+            if let Stmt::Var { name, .. } | Stmt::Function { name, .. } = statement {
+                if let Some(name) = name.symbol {
+                    exports.insert(name, environment.get(name)?);
+                }
+            }
+        }
+
+        Ok(exports)
     }
 
     #[instrument(skip(self), ret, level = "trace")]
-    pub fn execute_block(&mut self, statements: &Vec<Box<Stmt>>) -> Result<Return> {
+    pub fn execute_block(&mut self, statements: &[Box<Stmt>]) -> Result<Return> {
         for statement in statements {
-            let result = match execute(self, &*statement) {
+            let result = match execute(self, statement) {
                 Err(e) => Some(Err(e)),
                 Ok(Return::Value(r)) => Some(Ok(Return::Value(r))),
                 Ok(Return::Bare) => Some(Ok(Return::Bare)),
@@ -74,10 +503,42 @@ impl Interpreter {
 
         Ok(Return::None)
     }
+
+    fn execute_for(
+        &mut self,
+        token: &Token,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Result<Return> {
+        if let Some(initializer) = initializer {
+            execute(self, initializer)?;
+        }
+
+        while match condition {
+            Some(condition) => {
+                let entered = evaluate(self, condition)?.is_truthy();
+                self.cover_branch(token, entered);
+                entered
+            }
+            None => true,
+        } {
+            let ret = execute(self, body)?;
+            if ret.is_explicit() {
+                return Ok(ret);
+            }
+            if let Some(increment) = increment {
+                evaluate(self, increment)?;
+            }
+        }
+
+        Ok(Return::None)
+    }
 }
 
 impl StatementVisitor for &mut Interpreter {
-    fn visit_block(&mut self, stmts: &Vec<Box<Stmt>>) -> Result<Return> {
+    fn visit_block(&mut self, stmts: &[Box<Stmt>]) -> Result<Return> {
         (**self).visit_block(stmts)
     }
 
@@ -93,18 +554,34 @@ impl StatementVisitor for &mut Interpreter {
         // Disambiguate between StatementVisitor and ExpressionVisitor
         StatementVisitor::visit_variable(*self, name, initializer)
     }
-    fn visit_if(&mut self, condition: &Expr, then: &Stmt, els: Option<&Stmt>) -> Result<Return> {
-        (**self).visit_if(condition, then, els)
+    fn visit_if(
+        &mut self,
+        token: &Token,
+        condition: &Expr,
+        then: &Stmt,
+        els: Option<&Stmt>,
+    ) -> Result<Return> {
+        (**self).visit_if(token, condition, then, els)
+    }
+    fn visit_while(&mut self, token: &Token, condition: &Expr, body: &Stmt) -> Result<Return> {
+        (**self).visit_while(token, condition, body)
     }
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> Result<Return> {
-        (**self).visit_while(condition, body)
+    fn visit_for(
+        &mut self,
+        token: &Token,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Result<Return> {
+        (**self).visit_for(token, initializer, condition, increment, body)
     }
 
     fn visit_function(
         &mut self,
         name: &Token,
-        parameters: &Vec<Token>,
-        body: &Vec<Box<Stmt>>,
+        parameters: &[Token],
+        body: &[Box<Stmt>],
     ) -> Result<Return> {
         (**self).visit_function(name, parameters, body)
     }
@@ -112,11 +589,33 @@ impl StatementVisitor for &mut Interpreter {
     fn visit_return(&mut self, token: &Token, expr: Option<&Expr>) -> Result<Return> {
         (**self).visit_return(token, expr)
     }
+
+    fn visit_throw(&mut self, token: &Token, expr: &Expr) -> Result<Return> {
+        (**self).visit_throw(token, expr)
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &[Box<Stmt>],
+        catch: Option<(&Token, &[Box<Stmt>])>,
+        finally: Option<&[Box<Stmt>]>,
+    ) -> Result<Return> {
+        (**self).visit_try(body, catch, finally)
+    }
+
+    fn visit_import(
+        &mut self,
+        token: &Token,
+        path: &str,
+        names: Option<&[Token]>,
+    ) -> Result<Return> {
+        (**self).visit_import(token, path, names)
+    }
 }
 
 impl StatementVisitor for Interpreter {
     #[instrument(skip(self), ret, level = "trace")]
-    fn visit_block(&mut self, stmts: &Vec<Box<Stmt>>) -> Result<Return> {
+    fn visit_block(&mut self, stmts: &[Box<Stmt>]) -> Result<Return> {
         self.environment.new_scope();
         let result = self.execute_block(stmts);
         self.environment.end_scope();
@@ -126,7 +625,7 @@ impl StatementVisitor for Interpreter {
     #[instrument(skip(self), ret, level = "trace")]
     fn visit_print(&mut self, expr: &Expr) -> Result<Return> {
         let value = evaluate(self, expr)?;
-        println!("{}", value);
+        writeln!(self.output.0, "{}", value)?;
         Ok(Return::None)
     }
 
@@ -143,19 +642,28 @@ impl StatementVisitor for Interpreter {
         if let Some(initializer) = initializer {
             value = evaluate(self, initializer)?;
         }
-        let name = name
-            .lexeme
-            .clone()
-            .ok_or(RuntimeError::UnexpectedToken(name.clone()))?;
+        let line = name.line;
+        let name = symbol(name)?;
 
+        trace!(target: TRACE_TARGET, event = "define", line, name = name.as_str(), value = %value);
+        self.allocate(name.as_str().len() + std::mem::size_of::<Object>())?;
         self.environment.define(name, value);
 
         Ok(Return::None)
     }
 
     #[instrument(skip(self), ret, level = "trace")]
-    fn visit_if(&mut self, condition: &Expr, then: &Stmt, els: Option<&Stmt>) -> Result<Return> {
-        if evaluate(self, condition)?.is_truthy() {
+    fn visit_if(
+        &mut self,
+        token: &Token,
+        condition: &Expr,
+        then: &Stmt,
+        els: Option<&Stmt>,
+    ) -> Result<Return> {
+        let entered = evaluate(self, condition)?.is_truthy();
+        self.cover_branch(token, entered);
+
+        if entered {
             execute(self, then)
         } else if let Some(els) = els {
             execute(self, els)
@@ -165,8 +673,12 @@ impl StatementVisitor for Interpreter {
     }
 
     #[instrument(skip(self), ret, level = "trace")]
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> Result<Return> {
-        while evaluate(self, condition)?.is_truthy() {
+    fn visit_while(&mut self, token: &Token, condition: &Expr, body: &Stmt) -> Result<Return> {
+        while {
+            let entered = evaluate(self, condition)?.is_truthy();
+            self.cover_branch(token, entered);
+            entered
+        } {
             let ret = execute(self, body)?;
             if ret.is_explicit() {
                 return Ok(ret);
@@ -176,23 +688,42 @@ impl StatementVisitor for Interpreter {
         Ok(Return::None)
     }
 
+    #[instrument(skip(self), ret, level = "trace")]
+    fn visit_for(
+        &mut self,
+        token: &Token,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Result<Return> {
+        // The initializer's variable is scoped to the loop
+        self.environment.new_scope();
+        let result = self.execute_for(token, initializer, condition, increment, body);
+        self.environment.end_scope();
+        result
+    }
+
     #[instrument(skip(self), ret, level = "trace")]
     fn visit_function(
         &mut self,
         name: &Token,
-        arguments: &Vec<Token>,
-        body: &Vec<Box<Stmt>>,
+        arguments: &[Token],
+        body: &[Box<Stmt>],
     ) -> Result<Return> {
+        self.collect_if_due();
+        let closure = self.environment.capture();
+        self.allocate(std::mem::size_of::<Function>() + closure.captured_heap_size())?;
+
         let function = Function::new(
             name.clone(),
-            arguments.clone(),
-            body.clone(),
-            self.environment.clone(),
+            arguments.to_vec(),
+            body.to_vec(),
+            closure,
+            self.file.clone(),
         );
-        let name = name.lexeme.clone().unwrap();
-
         self.environment
-            .define(name, Object::Function(std::rc::Rc::new(function)));
+            .define(symbol(name)?, Object::Function(std::rc::Rc::new(function)));
 
         Ok(Return::None)
     }
@@ -200,74 +731,141 @@ impl StatementVisitor for Interpreter {
     #[instrument(skip(self), ret, level = "trace")]
     fn visit_return(&mut self, _token: &Token, expr: Option<&Expr>) -> Result<Return> {
         match expr {
-            Some(e) => Ok(Return::Value(evaluate(self, &e)?)),
+            Some(e) => Ok(Return::Value(evaluate(self, e)?)),
             None => Ok(Return::Bare),
         }
     }
+
+    #[instrument(skip(self), ret, level = "trace")]
+    fn visit_throw(&mut self, token: &Token, expr: &Expr) -> Result<Return> {
+        let value = evaluate(self, expr)?;
+        let error = RuntimeError::Thrown(value.to_string());
+        self.thrown = Some(value);
+
+        Err(anyhow::Error::from(error).context(Line(token.line)))
+    }
+
+    #[instrument(skip(self), ret, level = "trace")]
+    fn visit_try(
+        &mut self,
+        body: &[Box<Stmt>],
+        catch: Option<(&Token, &[Box<Stmt>])>,
+        finally: Option<&[Box<Stmt>]>,
+    ) -> Result<Return> {
+        let mut result = self.visit_block(body);
+
+        // Resource limits are enforced by the host, and `exit` ends the
+        // process, so scripts can't catch either
+        if let Err(error) = &result {
+            if error.is::<ResourceExhausted>() || error.is::<Exit>() {
+                return result;
+            }
+        }
+
+        if let Some((name, handler)) = catch {
+            if let Err(error) = result {
+                let name = symbol(name)?;
+                let value = self.caught(error);
+
+                self.allocate(name.as_str().len() + std::mem::size_of::<Object>())?;
+                self.environment.new_scope();
+                self.environment.define(name, value);
+                result = self.execute_block(handler);
+                self.environment.end_scope();
+            }
+        }
+
+        // Returning or throwing from `finally` replaces the pending outcome.
+        // Otherwise a value it throws and catches mustn't replace the one
+        // still unwinding.
+        if let Some(finally) = finally {
+            let pending = self.thrown.take();
+            match self.visit_block(finally)? {
+                Return::None => {}
+                ret => return Ok(ret),
+            }
+            self.thrown = pending;
+        }
+
+        result
+    }
+
+    #[instrument(skip(self), ret, level = "trace")]
+    fn visit_import(
+        &mut self,
+        token: &Token,
+        path: &str,
+        names: Option<&[Token]>,
+    ) -> Result<Return> {
+        let exports = match self.import(path) {
+            Err(error) if !error.is::<Line>() => return Err(error.context(Line(token.line))),
+            result => result?,
+        };
+
+        match names {
+            Some(names) => {
+                for name in names {
+                    let name = symbol(name)?;
+                    let value = exports.get(&name).cloned().ok_or_else(|| {
+                        anyhow::Error::from(RuntimeError::UndefinedExport(
+                            path.to_string(),
+                            name.to_string(),
+                        ))
+                        .context(Line(token.line))
+                    })?;
+                    self.environment.define(name, value);
+                }
+            }
+            None => {
+                for (name, value) in exports.iter() {
+                    self.environment.define(*name, value.clone());
+                }
+            }
+        }
+
+        Ok(Return::None)
+    }
 }
 
 impl ExpressionVisitor<Object> for Interpreter {
     #[instrument(skip(self), ret, level = "trace")]
-    fn visit_assignment(&mut self, name: &Token, expr: &Expr) -> Result<Object> {
+    fn visit_assignment(
+        &mut self,
+        name: &Token,
+        expr: &Expr,
+        slot: Option<Slot>,
+    ) -> Result<Object> {
         let value = evaluate(self, expr)?;
-        let lexeme = name
-            .lexeme
-            .clone()
-            .ok_or(RuntimeError::UnexpectedToken(name.clone()))?;
+        let line = name.line;
+        let name = symbol(name)?;
 
-        self.environment.assign(lexeme, value.clone())?;
-        // let name = Expr::Assign {
-        //     name: name.clone(),
-        //     value: Box::new(expr.clone()),
-        // };
-        // match self.locals.get(&name) {
-        //     Some(distance) => self
-        //         .environment
-        //         .assign_at(*distance, lexeme, value.clone())?,
-        //     None => self.environment.assign_global(lexeme, value.clone())?,
-        // }
+        trace!(target: TRACE_TARGET, event = "assign", line, name = name.as_str(), value = %value);
+        self.environment.assign_at(slot, name, value.clone())?;
 
         Ok(value)
     }
 
     #[instrument(skip(self), ret, level = "trace")]
     fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<Object> {
-        let left: Object = evaluate(self, left)?.try_into()?;
-        let right: Object = evaluate(self, right)?.try_into()?;
-
-        match operator.token_type {
-            TokenType::Minus => left - right,
-            TokenType::Slash => left / right,
-            TokenType::Star => left * right,
-            TokenType::Plus => left + right,
-            TokenType::Greater => match left.partial_cmp(&right) {
-                Some(b) => Ok(Object::Boolean(b.is_gt())),
-                None => Err(EvaluationError::Comparision(">".to_string()).into()),
-            },
-            TokenType::GreaterEqual => match left.partial_cmp(&right) {
-                Some(b) => Ok(Object::Boolean(b.is_ge())),
-                None => Err(EvaluationError::Comparision(">=".to_string()).into()),
-            },
-            TokenType::Less => match left.partial_cmp(&right) {
-                Some(b) => Ok(Object::Boolean(b.is_lt())),
-                None => Err(EvaluationError::Comparision("<".to_string()).into()),
-            },
-            TokenType::LessEqual => match left.partial_cmp(&right) {
-                Some(b) => Ok(Object::Boolean(b.is_le())),
-                None => Err(EvaluationError::Comparision("<=".to_string()).into()),
-            },
-            TokenType::EqualEqual => Ok(Object::Boolean(left.eq(&right))),
-            TokenType::BangEqual => Ok(Object::Boolean(!left.eq(&right))),
-            _ => Err(anyhow!("invalid operation")),
+        let left: Object = evaluate(self, left)?;
+        let right: Object = evaluate(self, right)?;
+
+        // Only concatenation makes a new string; comparisons share them
+        if let (TokenType::Plus, Object::String(l), Object::String(r)) =
+            (&operator.token_type, &left, &right)
+        {
+            self.allocate(l.len() + r.len())?;
         }
+
+        binary(left, operator, right)
     }
 
     #[instrument(skip(self), ret, level = "trace")]
     fn visit_call(
         &mut self,
         callee: &Expr,
-        _paren: &Token,
-        arguments: &Vec<Box<Expr>>,
+        paren: &Token,
+        arguments: &[Box<Expr>],
     ) -> Result<Object> {
         let callee = evaluate(self, callee)?;
 
@@ -281,21 +879,58 @@ impl ExpressionVisitor<Object> for Interpreter {
             _ => Err(anyhow!("attempting to call primitive as function")),
         }?;
 
-        match callee.call(self, &arguments)? {
-            Return::Value(e) => Ok(e),
-            Return::Bare => Ok(Object::Nil),
-            Return::None => Ok(Object::Nil), // TODO: What is the right thing to do here?
+        let name = callee.name();
+        trace!(target: TRACE_TARGET, event = "enter", line = paren.line, function = name);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(name.clone());
         }
+        let result = callee.call(self, &arguments);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit();
+        }
+
+        let value = match result {
+            Ok(Return::Value(e)) => e,
+            Ok(Return::Bare) => Object::Nil,
+            Ok(Return::None) => Object::Nil, // TODO: What is the right thing to do here?
+            Err(error) => {
+                trace!(target: TRACE_TARGET, event = "exit", function = name, error = %error.root_cause());
+                return Err(error);
+            }
+        };
+        trace!(target: TRACE_TARGET, event = "exit", function = name, value = %value);
+
+        Ok(value)
     }
 
     #[instrument(skip(self), ret, level = "trace")]
     fn visit_unary(&mut self, operator: &Token, right: &Expr) -> Result<Object> {
         let right = evaluate(self, right)?;
+        unary(operator, right)
+    }
+
+    #[instrument(skip(self), ret, level = "trace")]
+    fn visit_get(&mut self, object: &Expr, name: &Token) -> Result<Object> {
+        let object = evaluate(self, object)?;
+        let name = name
+            .lexeme
+            .clone()
+            .ok_or(RuntimeError::UnexpectedToken(name.clone()))?;
 
-        match operator.token_type {
-            TokenType::Minus => -right,
-            TokenType::Bang => Ok(!right),
-            _ => Err(anyhow!("invalid operation")),
+        match object {
+            Object::Error(error) => match name.as_str() {
+                "message" => Ok(Object::String(error.message.as_str().into())),
+                "line" => Ok(error
+                    .line
+                    .map_or(Object::Nil, |line| Object::Number(line as f64))),
+                _ => Err(RuntimeError::UndefinedProperty(name).into()),
+            },
+            Object::Namespace(namespace) => namespace
+                .members
+                .get(&name)
+                .cloned()
+                .ok_or(RuntimeError::UndefinedProperty(name).into()),
+            _ => Err(RuntimeError::NoProperties.into()),
         }
     }
 
@@ -304,6 +939,28 @@ impl ExpressionVisitor<Object> for Interpreter {
         evaluate(self, grouping)
     }
 
+    #[instrument(skip(self), ret, level = "trace")]
+    fn visit_lambda(
+        &mut self,
+        token: &Token,
+        parameters: &[Token],
+        body: &[Box<Stmt>],
+    ) -> Result<Object> {
+        self.collect_if_due();
+        let closure = self.environment.capture();
+        self.allocate(std::mem::size_of::<Function>() + closure.captured_heap_size())?;
+
+        let function = Function::new(
+            token.clone(),
+            parameters.to_vec(),
+            body.to_vec(),
+            closure,
+            self.file.clone(),
+        );
+
+        Ok(Object::Function(Rc::new(function)))
+    }
+
     #[instrument(skip(self), ret, level = "trace")]
     fn visit_literal(&mut self, literal: &Object) -> Result<Object> {
         Ok(literal.clone())
@@ -313,6 +970,12 @@ impl ExpressionVisitor<Object> for Interpreter {
     fn visit_logical(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<Object> {
         let left = evaluate(self, left)?;
 
+        let entered = match operator.token_type {
+            TokenType::Or => !left.is_truthy(),
+            _ => left.is_truthy(),
+        };
+        self.cover_branch(operator, entered);
+
         match (operator.token_type.clone(), left.is_truthy()) {
             (TokenType::Or, false) => evaluate(self, right),
             (TokenType::Or, true) | (_, false) => Ok(left),
@@ -321,29 +984,415 @@ impl ExpressionVisitor<Object> for Interpreter {
     }
 
     #[instrument(skip(self), ret, level = "trace")]
-    fn visit_variable(&mut self, token: &Token) -> Result<Object> {
-        self.environment.get(&token.lexeme.clone().unwrap())
-        // self.look_up_variable(token.clone())
+    fn visit_variable(&mut self, name: &Token, slot: Option<Slot>) -> Result<Object> {
+        self.environment.get_at(slot, symbol(name)?)
+    }
+}
+
+// The interned name of an identifier
+fn symbol(name: &Token) -> Result<Symbol> {
+    name.symbol
+        .ok_or_else(|| RuntimeError::UnexpectedToken(name.clone()).into())
+}
+
+/// Applies a binary operator to its evaluated operands.
+pub fn binary(left: Object, operator: &Token, right: Object) -> Result<Object> {
+    match operator.token_type {
+        TokenType::Minus => left - right,
+        TokenType::Slash => left / right,
+        TokenType::Star => left * right,
+        TokenType::Plus => left + right,
+        TokenType::Greater => match left.partial_cmp(&right) {
+            Some(b) => Ok(Object::Boolean(b.is_gt())),
+            None => Err(EvaluationError::Comparision(">".to_string()).into()),
+        },
+        TokenType::GreaterEqual => match left.partial_cmp(&right) {
+            Some(b) => Ok(Object::Boolean(b.is_ge())),
+            None => Err(EvaluationError::Comparision(">=".to_string()).into()),
+        },
+        TokenType::Less => match left.partial_cmp(&right) {
+            Some(b) => Ok(Object::Boolean(b.is_lt())),
+            None => Err(EvaluationError::Comparision("<".to_string()).into()),
+        },
+        TokenType::LessEqual => match left.partial_cmp(&right) {
+            Some(b) => Ok(Object::Boolean(b.is_le())),
+            None => Err(EvaluationError::Comparision("<=".to_string()).into()),
+        },
+        TokenType::EqualEqual => Ok(Object::Boolean(left.eq(&right))),
+        TokenType::BangEqual => Ok(Object::Boolean(!left.eq(&right))),
+        _ => Err(anyhow!("invalid operation")),
     }
 }
 
-fn evaluate<V, T>(visitor: &mut V, expression: &Expr) -> Result<T>
-where
-    V: ExpressionVisitor<T>,
-{
-    expression.accept(visitor)
+/// Applies a unary operator to its evaluated operand.
+pub fn unary(operator: &Token, right: Object) -> Result<Object> {
+    match operator.token_type {
+        TokenType::Minus => -right,
+        TokenType::Bang => Ok(!right),
+        _ => Err(anyhow!("invalid operation")),
+    }
 }
 
-// fn execute
-fn execute<V>(visitor: &mut V, statement: &Stmt) -> Result<Return>
-where
-    V: StatementVisitor,
-{
-    statement.accept(visitor)
+// Every evaluation and execution consumes a step from the interpreter's budget.
+// Errors are tagged with the line of the innermost expression they came from.
+fn evaluate(interpreter: &mut Interpreter, expression: &Expr) -> Result<Object> {
+    interpreter.tick()?;
+    expression
+        .accept(interpreter)
+        .map_err(|error| match expression.line() {
+            Some(line) if !error.is::<Line>() => error.context(Line(line)),
+            _ => error,
+        })
+}
+
+fn execute(interpreter: &mut Interpreter, statement: &Stmt) -> Result<Return> {
+    interpreter.tick()?;
+    trace!(target: TRACE_TARGET, event = "statement", line = statement.line(), kind = statement.kind());
+
+    if let Some(coverage) = &mut interpreter.coverage {
+        if let (Some(line), false) = (
+            statement.line(),
+            matches!(statement, Stmt::Block(_) | Stmt::Try { .. }),
+        ) {
+            coverage.statement(file(&interpreter.file), line);
+        }
+    }
+
+    // Taken while it runs, so expressions it evaluates don't reenter it
+    if let Some(mut debugger) = interpreter.debugger.take() {
+        let result = match statement {
+            Stmt::Block(_) => Ok(()),
+            _ => debugger.statement(interpreter, statement),
+        };
+        interpreter.debugger = Some(debugger);
+        result?;
+    }
+
+    statement.accept(interpreter)
 }
 
 #[cfg(test)]
 mod test {
+    use super::Interpreter;
+    use crate::data_types::Object;
+    use crate::error::{ResourceExhausted, RuntimeError};
+    use std::time::Duration;
+
+    fn run(interpreter: &mut Interpreter, source: &str) -> anyhow::Result<()> {
+        let tokens = crate::scanner::Scanner::new(source.to_string()).scan_tokens()?;
+        let statements = crate::parser::Parser::new(tokens).parse()?;
+        interpreter.interpret(statements)
+    }
+
+    #[test]
+    fn closures_share_globals() {
+        let mut interpreter = Interpreter::new();
+
+        // `fib` is defined after its closure is captured, and still found
+        let source = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+var result = fib(10);";
+        run(&mut interpreter, source).unwrap();
+        assert_eq!(
+            Object::Number(55.0),
+            interpreter.environment.get("result".into()).unwrap()
+        );
+    }
+
+    #[test]
+    fn assign_undefined() {
+        let mut interpreter = Interpreter::new();
+
+        for source in ["x = 1;", "{ x = 1; }"] {
+            let err = run(&mut interpreter, source).unwrap_err();
+            let expected = RuntimeError::UndefinedVariable("x".to_string());
+            assert_eq!(Some(&expected), err.downcast_ref());
+        }
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let mut interpreter = Interpreter::new().with_max_call_depth(16);
+
+        let err = run(&mut interpreter, "fun f() { f(); } f();").unwrap_err();
+        assert_eq!(Some(&RuntimeError::StackOverflow), err.downcast_ref());
+
+        // The call depth unwinds with the error, so the interpreter stays usable
+        assert!(run(&mut interpreter, "fun g(n) { if (n > 0) g(n - 1); } g(15);").is_ok());
+    }
+
+    #[test]
+    fn stack_overflow_on_small_stack() {
+        // Too small for the default depth of calls, in a debug build at least
+        let thread = std::thread::Builder::new().stack_size(8 * 1024 * 1024);
+        let overflowed = thread
+            .spawn(|| {
+                let err = run(&mut Interpreter::new(), "fun f() { f(); } f();").unwrap_err();
+                err.downcast_ref() == Some(&RuntimeError::StackOverflow)
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(overflowed);
+    }
+
+    #[test]
+    fn resource_exhausted() {
+        let mut interpreter = Interpreter::new().with_step_budget(1000);
+        let err = run(&mut interpreter, "while (true) {}").unwrap_err();
+        assert_eq!(Some(&ResourceExhausted::StepBudget), err.downcast_ref());
+        assert_eq!(None, err.downcast_ref::<RuntimeError>());
+
+        let mut interpreter = Interpreter::new().with_timeout(Duration::from_millis(10));
+        let err = run(&mut interpreter, "while (true) {}").unwrap_err();
+        assert_eq!(Some(&ResourceExhausted::Deadline), err.downcast_ref());
+    }
+
+    #[test]
+    fn try_catch() {
+        let mut interpreter = Interpreter::new();
+        let source = r#"
+            var message; var line;
+            try {
+                nil -
+                  1;
+            } catch (e) {
+                message = e.message;
+                line = e.line;
+            }
+
+            var order = "";
+            fun f() {
+                try {
+                    throw "thrown";
+                } catch (e) {
+                    order = order + e;
+                    return "catch";
+                } finally {
+                    order = order + " finally";
+                }
+            }
+            var returned = f();
+
+            fun g() {
+                try { return "try"; } finally { return "finally"; }
+            }
+            var overridden = g();
+
+            var nested;
+            try {
+                try { throw "A"; } finally { try { throw "B"; } catch (x) {} }
+            } catch (e) {
+                nested = e;
+            }
+        "#;
+        run(&mut interpreter, source).unwrap();
+
+        let get = |name: &str| interpreter.variable(name).unwrap();
+        assert_eq!(
+            Object::String("cannot perform subtract on non-numeric values".into()),
+            get("message")
+        );
+        assert_eq!(Object::Number(4.0), get("line"));
+        assert_eq!(Object::String("thrown finally".into()), get("order"));
+        assert_eq!(Object::String("catch".into()), get("returned"));
+        assert_eq!(Object::String("finally".into()), get("overridden"));
+        assert_eq!(Object::String("A".into()), get("nested"));
+
+        let err = run(&mut interpreter, "throw 42;").unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::Thrown("42".to_string())),
+            err.downcast_ref()
+        );
+    }
+
+    #[test]
+    fn resource_exhausted_is_not_catchable() {
+        let mut interpreter = Interpreter::new().with_step_budget(1000);
+        let source = "var caught = false; try { while (true) {} } catch (e) { caught = true; }";
+
+        let err = run(&mut interpreter, source).unwrap_err();
+        assert_eq!(Some(&ResourceExhausted::StepBudget), err.downcast_ref());
+        assert_eq!(
+            Object::Boolean(false),
+            interpreter.variable("caught").unwrap()
+        );
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let mut interpreter = Interpreter::new().with_max_heap(64 * 1024);
+        let before = interpreter.heap_usage();
+
+        run(&mut interpreter, "var s = \"abcdefgh\"; s = s + s;").unwrap();
+        assert!(interpreter.heap_usage() >= before + 16);
+
+        let err = run(&mut interpreter, "while (true) s = s + s;").unwrap_err();
+        assert_eq!(Some(&RuntimeError::OutOfMemory), err.downcast_ref());
+        assert!(interpreter.heap_usage() <= 64 * 1024);
+    }
+
+    #[test]
+    fn lambda() {
+        let mut interpreter = Interpreter::new();
+        let source = "fun apply(f, x) { return f(x); }
+                      var doubled = apply(fun (n) { return n * 2; }, 21);
+                      var offset = 1;
+                      var add = (a, b) => a + b + offset;
+                      var sum = add(2, 3);";
+
+        run(&mut interpreter, source).unwrap();
+        assert_eq!(
+            Object::Number(42.0),
+            interpreter.variable("doubled").unwrap()
+        );
+        assert_eq!(Object::Number(6.0), interpreter.variable("sum").unwrap());
+    }
+
+    #[test]
+    fn for_loop() {
+        let mut interpreter = Interpreter::new();
+        let source = "var sum = 0;
+                      for (var i = 1; i <= 4; i = i + 1) sum = sum + i;
+                      var j;
+                      for (j = 0; j < 3;) j = j + 1;
+                      fun first_over_ten() { for (var k = 1;; k = k + 1) if (k * k > 10) return k; }
+                      var root = first_over_ten();";
+
+        run(&mut interpreter, source).unwrap();
+        assert_eq!(
+            Object::Number(10.0),
+            interpreter.environment.get("sum".into()).unwrap()
+        );
+        assert_eq!(
+            Object::Number(3.0),
+            interpreter.environment.get("j".into()).unwrap()
+        );
+        assert_eq!(
+            Object::Number(4.0),
+            interpreter.environment.get("root".into()).unwrap()
+        );
+
+        // The initializer's variable is scoped to the loop
+        let err = run(
+            &mut interpreter,
+            "for (var i = 0; i < 1; i = i + 1) {} print i;",
+        )
+        .unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::UndefinedVariable("i".to_string())),
+            err.downcast_ref()
+        );
+    }
+
+    // Writes `files` into a fresh directory, returning the path of the first
+    fn write_modules(test: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("lox-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        for (name, source) in files {
+            let path = directory.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+
+        directory.join(files[0].0)
+    }
+
+    #[test]
+    fn import() {
+        let main = write_modules(
+            "import",
+            &[
+                (
+                    "main.lox",
+                    "import \"lib/a.lox\"; import { quad } from \"lib/b.lox\";
+                     var result = quad(double(1)); var count = loads;",
+                ),
+                (
+                    "lib/a.lox",
+                    "var loads = 0; loads = loads + 1; fun double(n) { return n * 2; }",
+                ),
+                (
+                    "lib/b.lox",
+                    "import { double } from \"a.lox\"; fun quad(n) { return double(double(n)); }",
+                ),
+            ],
+        );
+        let mut interpreter = Interpreter::new().with_script(&main);
+
+        run(&mut interpreter, &std::fs::read_to_string(&main).unwrap()).unwrap();
+        assert_eq!(Object::Number(8.0), interpreter.variable("result").unwrap());
+        // Both imports share a single execution of `a.lox`
+        assert_eq!(Object::Number(1.0), interpreter.variable("count").unwrap());
+        // Importing without a list binds every top-level definition
+        assert!(interpreter.variable("double").is_ok());
+        assert!(interpreter.variable("loads").is_ok());
+
+        let err = run(&mut interpreter, "import { nope } from \"lib/a.lox\";").unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::UndefinedExport(
+                "lib/a.lox".to_string(),
+                "nope".to_string()
+            )),
+            err.downcast_ref()
+        );
+
+        // Sandboxed scripts can't read modules either
+        let mut interpreter = Interpreter::new().with_script(&main).without_filesystem();
+        let err = run(&mut interpreter, "import \"lib/a.lox\";").unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::FilesystemDisabled("import".to_string())),
+            err.downcast_ref()
+        );
+    }
+
+    #[test]
+    fn import_from_function() {
+        let main = write_modules(
+            "import_from_function",
+            &[
+                (
+                    "main.lox",
+                    "import { load } from \"lib/a.lox\"; var result = load();",
+                ),
+                (
+                    "lib/a.lox",
+                    "fun load() { import { value } from \"b.lox\"; return value; }",
+                ),
+                ("lib/b.lox", "var value = \"lib\";"),
+            ],
+        );
+        let mut interpreter = Interpreter::new().with_script(&main);
+
+        // Resolved against `lib/a.lox`, which wrote the import, not `main.lox`
+        run(&mut interpreter, &std::fs::read_to_string(&main).unwrap()).unwrap();
+        assert_eq!(
+            Object::String("lib".into()),
+            interpreter.environment.get("result".into()).unwrap()
+        );
+    }
+
+    #[test]
+    fn import_cycle() {
+        let main = write_modules(
+            "import_cycle",
+            &[
+                ("main.lox", "import \"a.lox\";"),
+                ("a.lox", "import \"b.lox\";"),
+                ("b.lox", "import \"a.lox\";"),
+            ],
+        );
+        let mut interpreter = Interpreter::new().with_script(&main);
+
+        let err = run(&mut interpreter, "import \"a.lox\";").unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::ImportCycle(
+                "a.lox -> b.lox -> a.lox".to_string()
+            )),
+            err.downcast_ref()
+        );
+    }
+
     // #[test]
     // fn interpreter() {
     //     macro_rules! interpret {
@@ -0,0 +1,678 @@
+use crate::ast::{Expr, ExpressionVisitor, Slot, StatementVisitor, Stmt};
+use crate::data_types::{Object, Return};
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::token::{Token, TokenType};
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use anyhow::Result;
+
+/// A check made by `lox lint`, named in suppression comments such as
+/// `// lint: allow(unused-variable)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    UnusedVariable,
+    UnusedParameter,
+    Shadowing,
+    UnreachableCode,
+    UndeclaredAssignment,
+    SelfComparison,
+    ConstantCondition,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::UnusedVariable,
+        Rule::UnusedParameter,
+        Rule::Shadowing,
+        Rule::UnreachableCode,
+        Rule::UndeclaredAssignment,
+        Rule::SelfComparison,
+        Rule::ConstantCondition,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnusedParameter => "unused-parameter",
+            Rule::Shadowing => "shadowing",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::UndeclaredAssignment => "undeclared-assignment",
+            Rule::SelfComparison => "self-comparison",
+            Rule::ConstantCondition => "constant-condition",
+        }
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.name() == s)
+            .ok_or_else(|| format!("unknown lint rule '{}'", s))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub rule: Rule,
+    pub message: String,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "[line {}] Warning: {} ({})",
+            self.line, self.message, self.rule
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binding {
+    Variable,
+    Parameter,
+    // Never reported as unused, e.g. the name bound by `catch`
+    Unchecked,
+}
+
+#[derive(Debug)]
+struct Local {
+    line: usize,
+    binding: Binding,
+    used: bool,
+}
+
+type Scope = HashMap<String, Local>;
+
+/// Warns about likely mistakes that are still valid Lox.
+#[derive(Debug)]
+pub struct Linter {
+    enabled: HashSet<Rule>,
+    scopes: Vec<Scope>,
+    // Names declared at the top level of the script
+    globals: HashSet<String>,
+    // Whether an `import` without a list may have bound any other global
+    imports_everything: bool,
+    // The natives, which may be assigned to like any global
+    builtins: Interpreter,
+    warnings: Vec<Warning>,
+}
+
+impl Linter {
+    pub fn new() -> Self {
+        Linter {
+            enabled: Rule::ALL.into_iter().collect(),
+            scopes: Vec::new(),
+            globals: HashSet::new(),
+            imports_everything: false,
+            builtins: Interpreter::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_enabled(mut self, rules: &[Rule]) -> Self {
+        self.enabled = rules.iter().copied().collect();
+        self
+    }
+
+    pub fn with_disabled(mut self, rules: &[Rule]) -> Self {
+        for rule in rules {
+            self.enabled.remove(rule);
+        }
+        self
+    }
+
+    /// Returns the warnings for `source` ordered by line, leaving out those
+    /// suppressed by a `// lint: allow` comment.
+    pub fn lint(mut self, source: &str) -> Result<Vec<Warning>> {
+        let tokens = Scanner::new(source.to_string())
+            .with_comments()
+            .without_symbols()
+            .scan_tokens()?;
+        let (tokens, suppressions) = suppressions(tokens);
+        let statements = Parser::new(tokens).parse()?;
+
+        for statement in &statements {
+            self.declare_global(statement);
+        }
+        self.statements(&statements);
+
+        let mut warnings: Vec<Warning> = std::mem::take(&mut self.warnings)
+            .into_iter()
+            .filter(|warning| self.enabled.contains(&warning.rule))
+            .filter(|warning| {
+                !suppressions
+                    .get(&warning.line)
+                    .is_some_and(|rules| rules.is_empty() || rules.contains(&warning.rule))
+            })
+            .collect();
+        warnings.sort_by_key(|warning| warning.line);
+
+        Ok(warnings)
+    }
+
+    fn warn(&mut self, line: usize, rule: Rule, message: String) {
+        self.warnings.push(Warning {
+            line,
+            rule,
+            message,
+        });
+    }
+
+    fn declare_global(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Var { name, .. } | Stmt::Function { name, .. } => {
+                self.globals.insert(name.to_string());
+            }
+            Stmt::Import {
+                names: Some(names), ..
+            } => {
+                self.globals.extend(names.iter().map(Token::to_string));
+            }
+            Stmt::Import { names: None, .. } => self.imports_everything = true,
+            _ => {}
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(Scope::new());
+    }
+
+    fn end_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+
+        for (name, local) in scope {
+            if local.used || name.starts_with('_') {
+                continue;
+            }
+            match local.binding {
+                Binding::Variable => self.warn(
+                    local.line,
+                    Rule::UnusedVariable,
+                    format!("'{}' is never used", name),
+                ),
+                Binding::Parameter => self.warn(
+                    local.line,
+                    Rule::UnusedParameter,
+                    format!("parameter '{}' is never used", name),
+                ),
+                Binding::Unchecked => {}
+            }
+        }
+    }
+
+    // Declares a local, unless at the top level
+    fn declare(&mut self, name: &Token, binding: Binding) {
+        let name_str = name.to_string();
+        let outer = self
+            .scopes
+            .iter()
+            .rev()
+            .skip(1)
+            .find_map(|scope| scope.get(&name_str))
+            .map(|local| local.line);
+
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        scope.insert(
+            name_str.clone(),
+            Local {
+                line: name.line,
+                binding,
+                used: false,
+            },
+        );
+
+        if let Some(line) = outer {
+            self.warn(
+                name.line,
+                Rule::Shadowing,
+                format!("'{}' shadows the local declared on line {}", name_str, line),
+            );
+        }
+    }
+
+    fn local(&mut self, name: &str) -> Option<&mut Local> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+    }
+
+    fn statements<S: std::borrow::Borrow<Stmt>>(&mut self, statements: &[S]) {
+        let mut exited = false;
+
+        for statement in statements.iter().map(|s| s.borrow()) {
+            if exited {
+                if let Some(line) = statement.line() {
+                    self.warn(line, Rule::UnreachableCode, "unreachable code".to_string());
+                }
+                exited = false;
+            }
+            let _ = statement.accept(self);
+            if matches!(statement, Stmt::Return { .. } | Stmt::Throw { .. }) {
+                exited = true;
+            }
+        }
+    }
+
+    fn block(&mut self, statements: &[Box<Stmt>]) {
+        self.begin_scope();
+        self.statements(statements);
+        self.end_scope();
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        let _ = expr.accept(self);
+    }
+
+    fn function(&mut self, params: &[Token], body: &[Box<Stmt>]) {
+        self.begin_scope();
+        for param in params {
+            self.declare(param, Binding::Parameter);
+        }
+        self.statements(body);
+        self.end_scope();
+    }
+
+    fn condition(&mut self, condition: &Expr, looping: bool) {
+        // `while (true)` is the usual way to loop until a `return`
+        let forever = matches!(
+            condition,
+            Expr::Literal {
+                value: Object::Boolean(true),
+                ..
+            }
+        );
+
+        if constant(condition) && !(looping && forever) {
+            if let Some(line) = condition.line() {
+                self.warn(
+                    line,
+                    Rule::ConstantCondition,
+                    format!("condition '{}' is constant", condition),
+                );
+            }
+        }
+        self.expr(condition);
+    }
+}
+
+// Whether `expr` is made only of literals, so always has the same value
+fn constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal { .. } => true,
+        Expr::Grouping { grouping } => constant(grouping),
+        Expr::Unary { right, .. } => constant(right),
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            constant(left) && constant(right)
+        }
+        _ => false,
+    }
+}
+
+// Whether evaluating `expr` has no side effects, e.g. a variable or property
+fn pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Variable { .. } => true,
+        Expr::Get { object, .. } => pure(object),
+        Expr::Grouping { grouping } => pure(grouping),
+        _ => false,
+    }
+}
+
+// Separates comments from the code, keeping the lines their `lint: allow`
+// directives cover: their own line, or the next line of code when on a line
+// of their own. An empty rule list allows every rule.
+fn suppressions(tokens: Vec<Token>) -> (Vec<Token>, HashMap<usize, Vec<Rule>>) {
+    let mut code: Vec<Token> = Vec::new();
+    let mut suppressions: HashMap<usize, Vec<Rule>> = HashMap::new();
+    let mut pending: Vec<Vec<Rule>> = Vec::new();
+
+    for token in tokens {
+        match &token.token_type {
+            TokenType::Comment(text) => {
+                let Some(rules) = directive(text) else {
+                    continue;
+                };
+                match code.last() {
+                    Some(last) if last.line == token.line => {
+                        allow(&mut suppressions, token.line, rules)
+                    }
+                    _ => pending.push(rules),
+                }
+            }
+            _ => {
+                for rules in pending.drain(..) {
+                    allow(&mut suppressions, token.line, rules);
+                }
+                code.push(token);
+            }
+        }
+    }
+
+    (code, suppressions)
+}
+
+fn allow(suppressions: &mut HashMap<usize, Vec<Rule>>, line: usize, rules: Vec<Rule>) {
+    match suppressions.get_mut(&line) {
+        Some(allowed) if allowed.is_empty() => {}
+        Some(allowed) if rules.is_empty() => allowed.clear(),
+        Some(allowed) => allowed.extend(rules),
+        None => {
+            suppressions.insert(line, rules);
+        }
+    }
+}
+
+// Rules listed by `lint: allow(a, b)`, empty for a bare `lint: allow`. A list
+// naming an unknown rule isn't a directive, so a typo doesn't allow every rule
+fn directive(comment: &str) -> Option<Vec<Rule>> {
+    let rest = comment.trim().strip_prefix("lint: allow")?.trim();
+    if rest.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let list = rest.strip_prefix('(')?.strip_suffix(')')?;
+    list.split(',')
+        .map(|rule| rule.trim().parse().ok())
+        .collect()
+}
+
+impl StatementVisitor for Linter {
+    fn visit_block(&mut self, statements: &[Box<Stmt>]) -> Result<Return> {
+        self.block(statements);
+        Ok(Return::None)
+    }
+
+    fn visit_if(
+        &mut self,
+        _token: &Token,
+        condition: &Expr,
+        then: &Stmt,
+        els: Option<&Stmt>,
+    ) -> Result<Return> {
+        self.condition(condition, false);
+        then.accept(&mut *self)?;
+        if let Some(els) = els {
+            els.accept(&mut *self)?;
+        }
+        Ok(Return::None)
+    }
+
+    fn visit_print(&mut self, expr: &Expr) -> Result<Return> {
+        self.expr(expr);
+        Ok(Return::None)
+    }
+
+    fn visit_expression(&mut self, expr: &Expr) -> Result<Return> {
+        self.expr(expr);
+        Ok(Return::None)
+    }
+
+    fn visit_variable(&mut self, name: &Token, initializer: Option<&Expr>) -> Result<Return> {
+        if let Some(initializer) = initializer {
+            self.expr(initializer);
+        }
+        self.declare(name, Binding::Variable);
+        Ok(Return::None)
+    }
+
+    fn visit_while(&mut self, _token: &Token, condition: &Expr, body: &Stmt) -> Result<Return> {
+        self.condition(condition, true);
+        body.accept(self)
+    }
+
+    fn visit_for(
+        &mut self,
+        _token: &Token,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Result<Return> {
+        self.begin_scope();
+        if let Some(initializer) = initializer {
+            initializer.accept(&mut *self)?;
+        }
+        if let Some(condition) = condition {
+            self.condition(condition, true);
+        }
+        if let Some(increment) = increment {
+            self.expr(increment);
+        }
+        body.accept(&mut *self)?;
+        self.end_scope();
+
+        Ok(Return::None)
+    }
+
+    fn visit_function(
+        &mut self,
+        name: &Token,
+        params: &[Token],
+        body: &[Box<Stmt>],
+    ) -> Result<Return> {
+        self.declare(name, Binding::Variable);
+        self.function(params, body);
+        Ok(Return::None)
+    }
+
+    fn visit_return(&mut self, _token: &Token, expr: Option<&Expr>) -> Result<Return> {
+        if let Some(expr) = expr {
+            self.expr(expr);
+        }
+        Ok(Return::None)
+    }
+
+    fn visit_throw(&mut self, _token: &Token, expr: &Expr) -> Result<Return> {
+        self.expr(expr);
+        Ok(Return::None)
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &[Box<Stmt>],
+        catch: Option<(&Token, &[Box<Stmt>])>,
+        finally: Option<&[Box<Stmt>]>,
+    ) -> Result<Return> {
+        self.block(body);
+        if let Some((name, handler)) = catch {
+            self.begin_scope();
+            self.declare(name, Binding::Unchecked);
+            self.statements(handler);
+            self.end_scope();
+        }
+        if let Some(finally) = finally {
+            self.block(finally);
+        }
+        Ok(Return::None)
+    }
+
+    fn visit_import(
+        &mut self,
+        _token: &Token,
+        _path: &str,
+        names: Option<&[Token]>,
+    ) -> Result<Return> {
+        for name in names.unwrap_or_default() {
+            self.declare(name, Binding::Unchecked);
+        }
+        Ok(Return::None)
+    }
+}
+
+impl ExpressionVisitor<()> for Linter {
+    fn visit_assignment(&mut self, name: &Token, value: &Expr, _slot: Option<Slot>) -> Result<()> {
+        self.expr(value);
+
+        let name_str = name.to_string();
+        let declared = self.local(&name_str).is_some()
+            || self.globals.contains(&name_str)
+            || self.imports_everything
+            || self.builtins.variable(&name_str).is_ok();
+        if !declared {
+            self.warn(
+                name.line,
+                Rule::UndeclaredAssignment,
+                format!("assignment to undeclared variable '{}'", name_str),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn visit_binary(&mut self, left: &Expr, operator: &Token, right: &Expr) -> Result<()> {
+        let comparison = matches!(
+            operator.token_type,
+            TokenType::EqualEqual
+                | TokenType::BangEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+        );
+        if comparison && pure(left) && left.to_string() == right.to_string() {
+            self.warn(
+                operator.line,
+                Rule::SelfComparison,
+                format!("'{}' is compared with itself", left),
+            );
+        }
+
+        self.expr(left);
+        self.expr(right);
+        Ok(())
+    }
+
+    fn visit_call(&mut self, callee: &Expr, _paren: &Token, args: &[Box<Expr>]) -> Result<()> {
+        self.expr(callee);
+        for arg in args {
+            self.expr(arg);
+        }
+        Ok(())
+    }
+
+    fn visit_get(&mut self, object: &Expr, _name: &Token) -> Result<()> {
+        self.expr(object);
+        Ok(())
+    }
+
+    fn visit_grouping(&mut self, grouping: &Expr) -> Result<()> {
+        self.expr(grouping);
+        Ok(())
+    }
+
+    fn visit_lambda(&mut self, _token: &Token, params: &[Token], body: &[Box<Stmt>]) -> Result<()> {
+        self.function(params, body);
+        Ok(())
+    }
+
+    fn visit_unary(&mut self, _operator: &Token, right: &Expr) -> Result<()> {
+        self.expr(right);
+        Ok(())
+    }
+
+    fn visit_literal(&mut self, _literal: &Object) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_logical(&mut self, left: &Expr, _operator: &Token, right: &Expr) -> Result<()> {
+        self.expr(left);
+        self.expr(right);
+        Ok(())
+    }
+
+    fn visit_variable(&mut self, name: &Token, _slot: Option<Slot>) -> Result<()> {
+        if let Some(local) = self.local(&name.to_string()) {
+            local.used = true;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Linter, Rule};
+
+    fn lint(source: &str) -> Vec<(usize, Rule)> {
+        Linter::new()
+            .lint(source)
+            .unwrap()
+            .into_iter()
+            .map(|warning| (warning.line, warning.rule))
+            .collect()
+    }
+
+    #[test]
+    fn rules() {
+        let source = "fun f(a, b) {
+  var unused = 1;
+  {
+    var b = 2;
+    print b;
+  }
+  return a;
+  print a;
+}
+undeclared = 1;
+len = nil;
+var x = 1;
+if (x == x) print x;
+while (1 < 2) {}
+while (true) return;
+f(1, 2);";
+
+        assert_eq!(
+            vec![
+                (1, Rule::UnusedParameter),
+                (2, Rule::UnusedVariable),
+                (4, Rule::Shadowing),
+                (8, Rule::UnreachableCode),
+                (10, Rule::UndeclaredAssignment),
+                (13, Rule::SelfComparison),
+                (14, Rule::ConstantCondition),
+            ],
+            lint(source)
+        );
+    }
+
+    #[test]
+    fn suppressions() {
+        let source = "fun f(a, b) { // lint: allow(unused-parameter)
+  // lint: allow
+  var unused = 1;
+  var other = a == a; // lint: allow(self-comparison, unused-variable)
+  var _ignored = 2;
+}";
+        assert!(lint(source).is_empty());
+
+        // A misspelled rule doesn't allow the others
+        let source = "var a = 1;
+print a == a; // lint: allow(unused-varible)";
+        assert_eq!(vec![(2, Rule::SelfComparison)], lint(source));
+
+        let warnings = Linter::new()
+            .with_disabled(&[Rule::UnusedParameter])
+            .lint("fun f(a) {}")
+            .unwrap();
+        assert!(warnings.is_empty());
+    }
+}
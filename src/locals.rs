@@ -0,0 +1,274 @@
+// Resolves each variable to the slot it's stored in, mirroring the scopes the
+// interpreter creates. Variables that may have been defined by importing every
+// export of a module are left to be looked up by name.
+
+use crate::ast::{Expr, Slot, Stmt};
+use crate::symbol::Symbol;
+use crate::token::Token;
+
+/// Records the slot of every variable read or assigned in `statements`.
+pub fn resolve(statements: &mut [Stmt]) {
+    let mut locals = Locals::default();
+    for statement in statements {
+        locals.statement(statement);
+    }
+}
+
+#[derive(Default)]
+struct Locals {
+    // Local scopes, the innermost last; the top level is global
+    scopes: Vec<Scope>,
+}
+
+#[derive(Default)]
+struct Scope {
+    // In the order the interpreter adds them to its scope
+    names: Vec<Symbol>,
+    // Names declared before importing every export of a module, which adds
+    // names that can't be known until it runs
+    known: Option<usize>,
+}
+
+impl Locals {
+    fn block(&mut self, statements: &mut [Box<Stmt>]) {
+        self.scopes.push(Scope::default());
+        self.statements(statements);
+        self.scopes.pop();
+    }
+
+    fn statements(&mut self, statements: &mut [Box<Stmt>]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    // Parameters and the body share the call's scope
+    fn function(&mut self, params: &[Token], body: &mut [Box<Stmt>]) {
+        self.scopes.push(Scope::default());
+        for param in params {
+            self.declare(param);
+        }
+        self.statements(body);
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if let (Some(scope), Some(name)) = (self.scopes.last_mut(), name.symbol) {
+            if !scope.names.contains(&name) {
+                scope.names.push(name);
+            }
+        }
+    }
+
+    fn lookup(&self, name: &Token) -> Option<Slot> {
+        let name = name.symbol?;
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            match (scope.names.iter().position(|n| *n == name), scope.known) {
+                (Some(index), known) if known.is_none_or(|known| index < known) => {
+                    return Some(Slot::Local { depth, index })
+                }
+                (None, None) => {}
+                // The name may have been imported, or moved by names that were
+                _ => return None,
+            }
+        }
+        Some(Slot::Global)
+    }
+
+    fn statement(&mut self, statement: &mut Stmt) {
+        match statement {
+            Stmt::Block(body) => self.block(body),
+            Stmt::If {
+                condition,
+                then,
+                els,
+                ..
+            } => {
+                self.expression(condition);
+                self.statement(then);
+                if let Some(els) = els {
+                    self.statement(els);
+                }
+            }
+            // The function only sees its own name if it's global, as the
+            // closure is captured before the name is declared
+            Stmt::Function { name, params, body } => {
+                self.function(params, body);
+                self.declare(name);
+            }
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.expression(expr),
+            Stmt::Var { name, initializer } => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.declare(name);
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.expression(condition);
+                self.statement(body);
+            }
+            // The initializer's variable is scoped to the loop
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.scopes.push(Scope::default());
+                if let Some(initializer) = initializer {
+                    self.statement(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Stmt::Throw { value, .. } => self.expression(value),
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.block(body);
+                if let Some((name, handler)) = catch {
+                    self.scopes.push(Scope::default());
+                    self.declare(name);
+                    self.statements(handler);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.block(finally);
+                }
+            }
+            Stmt::Import { names, .. } => match names {
+                Some(names) => {
+                    for name in names.iter() {
+                        self.declare(name);
+                    }
+                }
+                None => {
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.known.get_or_insert(scope.names.len());
+                    }
+                }
+            },
+        }
+    }
+
+    fn expression(&mut self, expression: &mut Expr) {
+        match expression {
+            Expr::Variable { name, slot } => *slot = self.lookup(name),
+            Expr::Assign { name, value, slot } => {
+                self.expression(value);
+                *slot = self.lookup(name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expr::Get { object: expr, .. }
+            | Expr::Grouping { grouping: expr }
+            | Expr::Unary { right: expr, .. } => self.expression(expr),
+            Expr::Lambda { params, body, .. } => self.function(params, body),
+            Expr::Literal { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::resolve;
+    use crate::ast::{Expr, Slot, Stmt};
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let tokens = crate::scanner::Scanner::new(source.to_string())
+            .scan_tokens()
+            .unwrap();
+        crate::parser::Parser::new(tokens).parse().unwrap()
+    }
+
+    // Slots of the variables printed, in order
+    fn printed(statements: &[Stmt], slots: &mut Vec<Option<Slot>>) {
+        for statement in statements {
+            match statement {
+                Stmt::Print(expr) => match &**expr {
+                    Expr::Variable { slot, .. } => slots.push(*slot),
+                    _ => unreachable!(),
+                },
+                Stmt::Block(body) | Stmt::Function { body, .. } => {
+                    let body: Vec<Stmt> = body.iter().map(|stmt| (**stmt).clone()).collect();
+                    printed(&body, slots);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn slots() {
+        let mut statements = parse(
+            "\
+var global = 1;
+print global;
+{
+  var a = 1;
+  var b = 2;
+  var a = 3;
+  print b;
+  fun f(x) {
+    print x;
+    print a;
+    print f;
+  }
+  {
+    print a;
+    import \"module.lox\";
+    print a;
+    var c = 4;
+    print c;
+  }
+}
+",
+        );
+        resolve(&mut statements);
+
+        let mut slots = Vec::new();
+        printed(&statements, &mut slots);
+        let slot = |depth, index| Some(Slot::Local { depth, index });
+        assert_eq!(
+            vec![
+                Some(Slot::Global),
+                slot(0, 1),
+                slot(0, 0),
+                slot(1, 0),
+                // Not yet declared when the closure is captured
+                Some(Slot::Global),
+                slot(1, 0),
+                // The import could define its own `a`
+                None,
+                None,
+            ],
+            slots
+        );
+    }
+}
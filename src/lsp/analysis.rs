@@ -0,0 +1,369 @@
+use crate::ast::Stmt;
+use crate::data_types::Object;
+use crate::error::{Line, ParseError};
+use crate::interpreter::Interpreter;
+use crate::lint::Linter;
+use crate::parser::Parser;
+use crate::resolver::{Binding, Resolver};
+use crate::scanner::Scanner;
+use crate::token::{Token, TokenType};
+
+use std::borrow::Borrow;
+
+const KEYWORDS: [&str; 21] = [
+    "and", "catch", "class", "else", "false", "finally", "for", "fun", "if", "import", "nil", "or",
+    "print", "return", "super", "this", "throw", "true", "try", "var", "while",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub message: String,
+    pub severity: Severity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Function,
+    Variable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: Token,
+    pub kind: SymbolKind,
+    // Declarations at the top level of a function's body
+    pub children: Vec<Symbol>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionKind {
+    Keyword,
+    Variable,
+    Native,
+}
+
+/// What the language server knows about one version of a document. Lines are
+/// numbered from 1 and columns from 0, like tokens.
+#[derive(Debug)]
+pub struct Analysis {
+    tokens: Vec<Token>,
+    statements: Vec<Stmt>,
+    declarations: Vec<Token>,
+    bindings: Vec<Binding>,
+    pub diagnostics: Vec<Diagnostic>,
+    // Holds the natives
+    builtins: Interpreter,
+}
+
+impl Analysis {
+    pub fn new(source: &str) -> Analysis {
+        let mut analysis = Analysis {
+            tokens: Vec::new(),
+            statements: Vec::new(),
+            declarations: Vec::new(),
+            bindings: Vec::new(),
+            diagnostics: Vec::new(),
+            builtins: Interpreter::new(),
+        };
+
+        match Scanner::new(source.to_string())
+            .without_symbols()
+            .scan_tokens()
+        {
+            Ok(tokens) => analysis.tokens = tokens,
+            Err(error) => {
+                analysis.error(error);
+                return analysis;
+            }
+        }
+
+        // Statements around a syntax error are still analysed, so navigation
+        // keeps working while typing
+        match Parser::new(analysis.tokens.clone()).parse_recovering() {
+            Ok((statements, errors)) => {
+                analysis.statements = statements;
+                if !errors.is_empty() {
+                    analysis.error(ParseError::Syntax(errors).into());
+                }
+            }
+            Err(error) => {
+                analysis.error(error);
+                return analysis;
+            }
+        }
+
+        // Each statement is resolved separately so one error doesn't hide
+        // the bindings of the rest
+        let mut resolver = Resolver::new();
+        let errors: Vec<_> = analysis
+            .statements
+            .iter()
+            .filter_map(|statement| resolver.resolve_stmt(statement).err())
+            .collect();
+        for error in errors {
+            analysis.error(error);
+        }
+        analysis.declarations = resolver.declarations().to_vec();
+        analysis.bindings = resolver.bindings();
+
+        if let Ok(warnings) = Linter::new().lint(source) {
+            analysis
+                .diagnostics
+                .extend(warnings.into_iter().map(|warning| Diagnostic {
+                    line: warning.line,
+                    message: format!("{} ({})", warning.message, warning.rule),
+                    severity: Severity::Warning,
+                }));
+        }
+
+        analysis
+    }
+
+    fn error(&mut self, error: anyhow::Error) {
+        let errors = match error.downcast_ref::<ParseError>() {
+            Some(ParseError::Syntax(errors)) => errors
+                .iter()
+                .map(|error| (error.line, error.message.clone()))
+                .collect(),
+            Some(ParseError::UnterminatedString(location)) => {
+                vec![(location.line, "unterminated string".to_string())]
+            }
+            Some(ParseError::UnexpectedCharacter(_, line)) => vec![(*line, error.to_string())],
+            _ => {
+                let line = error.downcast_ref::<Line>().map_or(1, |line| line.0);
+                vec![(line, error.root_cause().to_string())]
+            }
+        };
+
+        self.diagnostics
+            .extend(errors.into_iter().map(|(line, message)| Diagnostic {
+                line,
+                message,
+                severity: Severity::Error,
+            }));
+    }
+
+    /// The identifier at, or ending at, the given position.
+    pub fn token_at(&self, line: usize, column: usize) -> Option<&Token> {
+        self.tokens.iter().find(|token| {
+            let length = match &token.token_type {
+                TokenType::Identifier(name) => name.chars().count(),
+                _ => return false,
+            };
+            token.line == line && (token.column..=token.column + length).contains(&column)
+        })
+    }
+
+    /// The declaration `token` refers to, which is itself for a declaration.
+    pub fn definition(&self, token: &Token) -> Option<Token> {
+        if self.declarations.contains(token) {
+            return Some(token.clone());
+        }
+
+        self.bindings
+            .iter()
+            .find(|binding| binding.usage == *token)
+            .and_then(|binding| binding.declaration.clone())
+    }
+
+    /// The declaration of `token` followed by every use of it.
+    pub fn references(&self, token: &Token) -> Vec<Token> {
+        let Some(declaration) = self.definition(token) else {
+            return Vec::new();
+        };
+
+        let usages = self
+            .bindings
+            .iter()
+            .filter(|binding| binding.declaration.as_ref() == Some(&declaration))
+            .map(|binding| binding.usage.clone());
+        std::iter::once(declaration.clone()).chain(usages).collect()
+    }
+
+    /// Lox source describing what `token` refers to, e.g. `fun add(a, b)`.
+    pub fn hover(&self, token: &Token) -> Option<String> {
+        let name = token.lexeme.clone()?;
+
+        let Some(declaration) = self.definition(token) else {
+            return match self.builtins.variable(&name) {
+                Ok(Object::Namespace(_)) => Some(format!("// native namespace\n{}", name)),
+                Ok(_) => Some(format!("// native\n{}", name)),
+                Err(_) => None,
+            };
+        };
+
+        let index = self.tokens.iter().position(|t| *t == declaration)?;
+        let before = |n: usize| index.checked_sub(n).map(|i| &self.tokens[i].token_type);
+
+        let description = match (before(2), before(1)) {
+            (_, Some(TokenType::Var)) => format!("var {}", name),
+            (_, Some(TokenType::Fun)) => {
+                let params: Vec<String> = self.tokens[index + 1..]
+                    .iter()
+                    .skip(1)
+                    .take_while(|t| t.token_type != TokenType::RightParen)
+                    .filter(|t| matches!(t.token_type, TokenType::Identifier(_)))
+                    .map(Token::to_string)
+                    .collect();
+                format!("fun {}({})", name, params.join(", "))
+            }
+            (Some(TokenType::Catch), Some(TokenType::LeftParen)) => format!("catch ({})", name),
+            _ if self.imported(index) => format!("import {{ {} }}", name),
+            _ => format!("// parameter\n{}", name),
+        };
+
+        Some(format!(
+            "{}\n// declared on line {}",
+            description, declaration.line
+        ))
+    }
+
+    // Whether the token at `index` is in the list of an `import`
+    fn imported(&self, index: usize) -> bool {
+        let mut before = self.tokens[..index]
+            .iter()
+            .rev()
+            .skip_while(|t| matches!(t.token_type, TokenType::Identifier(_) | TokenType::Comma))
+            .map(|t| &t.token_type);
+
+        matches!(
+            (before.next(), before.next()),
+            (Some(TokenType::LeftBrace), Some(TokenType::Import))
+        )
+    }
+
+    /// The functions and variables declared at the top level, with those
+    /// declared in each function's body.
+    pub fn symbols(&self) -> Vec<Symbol> {
+        symbols(&self.statements)
+    }
+
+    /// Names that can be written at the given position: locals of enclosing
+    /// blocks, globals, natives and keywords. Works from the tokens alone, so
+    /// it still works while the document doesn't parse.
+    pub fn completions(&self, line: usize, column: usize) -> Vec<(String, CompletionKind)> {
+        let mut names: Vec<(String, CompletionKind)> = Vec::new();
+        let mut add = |name: String, kind| {
+            if !names.iter().any(|(n, _)| *n == name) {
+                names.push((name, kind));
+            }
+        };
+
+        let end = self
+            .tokens
+            .iter()
+            .position(|t| (t.line, t.column) >= (line, column))
+            .unwrap_or(self.tokens.len());
+        for name in self.locals(&self.tokens[..end]).into_iter().rev() {
+            add(name, CompletionKind::Variable);
+        }
+        for name in self.globals() {
+            add(name, CompletionKind::Variable);
+        }
+
+        let mut natives = self.builtins.global_names();
+        natives.sort();
+        for name in natives {
+            add(name, CompletionKind::Native);
+        }
+        for keyword in KEYWORDS {
+            add(keyword.to_string(), CompletionKind::Keyword);
+        }
+
+        names
+    }
+
+    // Names declared outside of any braces
+    fn globals(&self) -> Vec<String> {
+        let mut depth = 0usize;
+        let mut globals = Vec::new();
+
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token.token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth = depth.saturating_sub(1),
+                TokenType::Var | TokenType::Fun if depth == 0 => {
+                    globals.extend(identifier(self.tokens.get(i + 1)));
+                }
+                _ => {}
+            }
+        }
+
+        globals
+    }
+
+    // Names declared in the blocks still open at the end of `tokens`,
+    // innermost last
+    fn locals(&self, tokens: &[Token]) -> Vec<String> {
+        let mut scopes: Vec<Vec<String>> = Vec::new();
+        // Parameters, bound in the block that follows them
+        let mut pending = Vec::new();
+
+        for (i, token) in tokens.iter().enumerate() {
+            match token.token_type {
+                TokenType::LeftBrace => scopes.push(std::mem::take(&mut pending)),
+                TokenType::RightBrace => {
+                    scopes.pop();
+                }
+                TokenType::Var => {
+                    if let Some(scope) = scopes.last_mut() {
+                        scope.extend(identifier(tokens.get(i + 1)));
+                    }
+                }
+                TokenType::Fun | TokenType::Catch => {
+                    let mut rest = tokens[i + 1..].iter().peekable();
+                    if let Some(name) =
+                        rest.next_if(|t| matches!(t.token_type, TokenType::Identifier(_)))
+                    {
+                        if let (Some(scope), TokenType::Fun) =
+                            (scopes.last_mut(), &token.token_type)
+                        {
+                            scope.push(name.to_string());
+                        }
+                    }
+                    pending = rest
+                        .skip(1)
+                        .take_while(|t| t.token_type != TokenType::RightParen)
+                        .filter_map(|t| identifier(Some(t)))
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+
+        scopes.into_iter().flatten().chain(pending).collect()
+    }
+}
+
+fn identifier(token: Option<&Token>) -> Option<String> {
+    match &token?.token_type {
+        TokenType::Identifier(name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+fn symbols<S: Borrow<Stmt>>(statements: &[S]) -> Vec<Symbol> {
+    statements
+        .iter()
+        .filter_map(|statement| match statement.borrow() {
+            Stmt::Function { name, body, .. } => Some(Symbol {
+                name: name.clone(),
+                kind: SymbolKind::Function,
+                children: symbols(body),
+            }),
+            Stmt::Var { name, .. } => Some(Symbol {
+                name: name.clone(),
+                kind: SymbolKind::Variable,
+                children: Vec::new(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,358 @@
+// A Language Server Protocol server over stdio, see
+// https://microsoft.github.io/language-server-protocol/. Documents are synced
+// in full and positions count characters rather than UTF-16 code units.
+
+mod analysis;
+
+use analysis::{Analysis, CompletionKind, Severity, Symbol, SymbolKind};
+
+use crate::rpc;
+use crate::token::Token;
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+
+// JSON-RPC error code for requests the server doesn't handle
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Answers requests read from `input` until the client sends `exit`.
+pub fn serve(input: impl BufRead, output: impl Write) -> Result<()> {
+    Server {
+        input,
+        output,
+        documents: HashMap::new(),
+    }
+    .run()
+}
+
+#[derive(Debug)]
+struct Document {
+    text: String,
+    analysis: Analysis,
+}
+
+struct Server<R, W> {
+    input: R,
+    output: W,
+    documents: HashMap<String, Document>,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    fn run(&mut self) -> Result<()> {
+        while let Some(message) = self.read()? {
+            let method = message["method"].as_str().unwrap_or_default();
+            let params = &message["params"];
+
+            match (method, message.get("id")) {
+                ("exit", _) => break,
+                (method, Some(id)) => {
+                    let response = match self.request(method, params) {
+                        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                        Err(error) => json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": {"code": METHOD_NOT_FOUND, "message": error.to_string()},
+                        }),
+                    };
+                    self.write(&response)?;
+                }
+                (method, None) => self.notification(method, params)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<Option<Value>> {
+        rpc::read(&mut self.input)
+    }
+
+    fn write(&mut self, message: &Value) -> Result<()> {
+        rpc::write(&mut self.output, message)
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": {"name": "lox"},
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/definition" => Ok(match self.token_at(params) {
+                Some((uri, document, token)) => document
+                    .analysis
+                    .definition(&token)
+                    .map_or(Value::Null, |declaration| location(uri, &declaration)),
+                None => Value::Null,
+            }),
+            "textDocument/references" => Ok(match self.token_at(params) {
+                Some((uri, document, token)) => {
+                    let declaration = params["context"]["includeDeclaration"].as_bool();
+                    let references = document.analysis.references(&token);
+                    let skip = usize::from(declaration == Some(false));
+                    references
+                        .iter()
+                        .skip(skip)
+                        .map(|reference| location(uri, reference))
+                        .collect()
+                }
+                None => json!([]),
+            }),
+            "textDocument/hover" => Ok(match self.token_at(params) {
+                Some((_, document, token)) => match document.analysis.hover(&token) {
+                    Some(hover) => json!({
+                        "contents": {"kind": "markdown", "value": format!("```lox\n{}\n```", hover)},
+                        "range": range(&token),
+                    }),
+                    None => Value::Null,
+                },
+                None => Value::Null,
+            }),
+            "textDocument/documentSymbol" => {
+                let document = self.document(params)?;
+                Ok(document.analysis.symbols().iter().map(symbol).collect())
+            }
+            "textDocument/completion" => {
+                let (line, column) = position(params)?;
+                let document = self.document(params)?;
+                let items: Vec<Value> = document
+                    .analysis
+                    .completions(line, column)
+                    .into_iter()
+                    .map(|(label, kind)| {
+                        let kind = match kind {
+                            CompletionKind::Keyword => 14,
+                            CompletionKind::Variable => 6,
+                            CompletionKind::Native => 3,
+                        };
+                        json!({"label": label, "kind": kind})
+                    })
+                    .collect();
+                Ok(json!(items))
+            }
+            _ => Err(anyhow!("unsupported method '{}'", method)),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // Full sync, so the last change holds the whole document
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return self.publish(uri);
+            }
+            _ => return Ok(()),
+        };
+
+        if let Some(text) = text {
+            let document = Document {
+                text: text.to_string(),
+                analysis: Analysis::new(text),
+            };
+            self.documents.insert(uri.to_string(), document);
+            self.publish(uri)?;
+        }
+
+        Ok(())
+    }
+
+    fn publish(&mut self, uri: &str) -> Result<()> {
+        let diagnostics: Vec<Value> = match self.documents.get(uri) {
+            Some(document) => document
+                .analysis
+                .diagnostics
+                .iter()
+                .map(|diagnostic| {
+                    let line = diagnostic.line.saturating_sub(1);
+                    let length = document
+                        .text
+                        .lines()
+                        .nth(line)
+                        .map_or(0, |l| l.chars().count());
+                    let severity = match diagnostic.severity {
+                        Severity::Error => 1,
+                        Severity::Warning => 2,
+                    };
+                    json!({
+                        "range": {
+                            "start": {"line": line, "character": 0},
+                            "end": {"line": line, "character": length},
+                        },
+                        "severity": severity,
+                        "source": "lox",
+                        "message": diagnostic.message,
+                    })
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        self.write(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": diagnostics},
+        }))
+    }
+
+    fn document(&self, params: &Value) -> Result<&Document> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        self.documents
+            .get(uri)
+            .with_context(|| format!("unknown document '{}'", uri))
+    }
+
+    // The identifier at the position of a request
+    fn token_at<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a Document, Token)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let document = self.documents.get(uri)?;
+        let (line, column) = position(params).ok()?;
+        let token = document.analysis.token_at(line, column)?.clone();
+        Some((uri, document, token))
+    }
+}
+
+// The position of a request, in token coordinates
+fn position(params: &Value) -> Result<(usize, usize)> {
+    let position = &params["position"];
+    match (position["line"].as_u64(), position["character"].as_u64()) {
+        (Some(line), Some(character)) => Ok((line as usize + 1, character as usize)),
+        _ => Err(anyhow!("request without a position")),
+    }
+}
+
+fn range(token: &Token) -> Value {
+    let length = token.lexeme.as_deref().map_or(1, |l| l.chars().count());
+    json!({
+        "start": {"line": token.line - 1, "character": token.column},
+        "end": {"line": token.line - 1, "character": token.column + length},
+    })
+}
+
+fn location(uri: &str, token: &Token) -> Value {
+    json!({"uri": uri, "range": range(token)})
+}
+
+fn symbol(symbol: &Symbol) -> Value {
+    let kind = match symbol.kind {
+        SymbolKind::Function => 12,
+        SymbolKind::Variable => 13,
+    };
+    json!({
+        "name": symbol.name.to_string(),
+        "kind": kind,
+        "range": range(&symbol.name),
+        "selectionRange": range(&symbol.name),
+        "children": symbol.children.iter().map(self::symbol).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::serve;
+    use serde_json::{json, Value};
+
+    // Frames `messages` as a client would and returns the server's replies
+    fn exchange(messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for message in messages {
+            let body = message.to_string();
+            input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+        }
+
+        let mut output = Vec::new();
+        serve(input.as_slice(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        output
+            .split("Content-Length: ")
+            .filter(|message| !message.is_empty())
+            .map(|message| serde_json::from_str(message.split_once("\r\n\r\n").unwrap().1).unwrap())
+            .collect()
+    }
+
+    fn request(id: u64, method: &str, line: u64, character: u64) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": {
+                "textDocument": {"uri": "file:///a.lox"},
+                "position": {"line": line, "character": character},
+                "context": {"includeDeclaration": true},
+            },
+        })
+    }
+
+    #[test]
+    fn session() {
+        let text = "fun add(a, b) {\n  return a + b;\n}\nprint add(1, 2);\nvar x = ;\n";
+        let replies = exchange(&[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": {"textDocument": {"uri": "file:///a.lox", "text": text}},
+            }),
+            request(2, "textDocument/definition", 3, 7),
+            request(3, "textDocument/references", 1, 9),
+            request(4, "textDocument/hover", 3, 7),
+            request(5, "textDocument/completion", 1, 2),
+            json!({"jsonrpc": "2.0", "id": 6, "method": "shutdown"}),
+            json!({"jsonrpc": "2.0", "method": "exit"}),
+        ]);
+
+        assert_eq!(true, replies[0]["result"]["capabilities"]["hoverProvider"]);
+
+        let diagnostics = &replies[1]["params"]["diagnostics"];
+        assert_eq!(4, diagnostics[0]["range"]["start"]["line"]);
+        assert_eq!(1, diagnostics[0]["severity"]);
+
+        let definition = &replies[2]["result"]["range"]["start"];
+        assert_eq!(json!({"line": 0, "character": 4}), *definition);
+
+        let references: Vec<&Value> = replies[3]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|location| &location["range"]["start"])
+            .collect();
+        assert_eq!(
+            vec![
+                &json!({"line": 0, "character": 8}),
+                &json!({"line": 1, "character": 9}),
+            ],
+            references
+        );
+
+        let hover = replies[4]["result"]["contents"]["value"].as_str().unwrap();
+        assert!(hover.contains("fun add(a, b)"));
+
+        let labels: Vec<&str> = replies[5]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(vec!["b", "a", "add"], labels[..3]);
+        assert!(labels.contains(&"len") && labels.contains(&"while"));
+
+        assert_eq!(Value::Null, replies[6]["result"]);
+    }
+}
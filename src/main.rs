@@ -1,71 +1,546 @@
+use std::fs::File;
 use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
 
-use crate::interpreter::Interpreter;
+use crate::coverage::Coverage;
+use crate::debugger::Console;
+use crate::error::Exit;
+use crate::interpreter::{Interpreter, DEFAULT_MAX_CALL_DEPTH, TRACE_TARGET};
+use crate::lint::{Linter, Rule};
+use crate::resolver::Resolver;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
 
+use tracing::Level;
 use tracing_flame::FlameLayer;
+use tracing_subscriber::filter::Targets;
 use tracing_subscriber::prelude::*;
 
 mod ast;
+mod coverage;
+mod dap;
 mod data_types;
+mod debugger;
 mod environment;
 mod error;
+mod formatter;
+mod gc;
+mod heap;
 mod interpreter;
+mod lint;
+mod locals;
+mod lsp;
+mod module;
+mod natives;
+mod optimizer;
 mod parser;
-// mod resolver;
+mod profiler;
+mod resolver;
+mod rpc;
 mod scanner;
+mod symbol;
+mod testing;
 mod token;
 
+// Rust stack reserved per nested Lox call; generous enough for debug builds.
+const STACK_PER_CALL: usize = 64 * 1024;
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "A tree-walking interpreter for Lox",
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    // `lox [script]` is short for `lox run [script]`
+    #[command(flatten)]
+    run: Run,
+
+    /// Write a folded-stack profile of the interpreter, for flamegraph tools
+    #[arg(long, global = true, value_name = "FILE")]
+    flamegraph: Option<PathBuf>,
+
+    /// Write one JSON event per executed statement, call, return and
+    /// assignment to this file
+    #[arg(long, global = true, value_name = "FILE")]
+    trace: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a script, or start a REPL when given none
+    Run(Run),
+    /// Start an interactive session
+    Repl(Limits),
+    /// Run a script under a debugger reading commands from stdin, paused
+    /// before the first statement; `help` lists the commands
+    Debug {
+        script: PathBuf,
+
+        /// Arguments passed to the script as `ARGS`
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        arguments: Vec<String>,
+
+        #[command(flatten)]
+        limits: Limits,
+    },
+    /// Run the `test_*` functions of the `.lox` files under the paths, each
+    /// in a fresh interpreter, exiting with 1 if any fail
+    Test {
+        #[arg(default_value = ".")]
+        paths: Vec<PathBuf>,
+
+        /// Only run the tests whose `file::name` contains this
+        #[arg(long)]
+        filter: Option<String>,
+
+        #[command(flatten)]
+        limits: Limits,
+    },
+    /// Scan, parse and resolve a script without running it
+    Check { script: PathBuf },
+    /// Print the tokens of a script
+    Tokens { script: PathBuf },
+    /// Print the syntax tree of a script
+    Ast {
+        script: PathBuf,
+
+        /// Print the tree after folding constants and removing dead branches
+        #[arg(long)]
+        optimize: bool,
+    },
+    /// Warn about likely mistakes in scripts, exiting with 1 if there are any.
+    /// Rules: unused-variable, unused-parameter, shadowing, unreachable-code,
+    /// undeclared-assignment, self-comparison and constant-condition
+    Lint {
+        /// Only check these rules
+        #[arg(long, value_name = "RULE")]
+        enable: Vec<Rule>,
+
+        /// Don't check these rules
+        #[arg(long, value_name = "RULE")]
+        disable: Vec<Rule>,
+
+        #[arg(required = true)]
+        scripts: Vec<PathBuf>,
+    },
+    /// Rewrite scripts in canonical formatting; `-` formats stdin to stdout
+    Fmt {
+        /// List the scripts that need formatting instead of rewriting them,
+        /// exiting with 1 if there are any
+        #[arg(long)]
+        check: bool,
+
+        #[arg(required = true)]
+        scripts: Vec<PathBuf>,
+    },
+    /// Run a language server over stdin and stdout
+    Lsp,
+    /// Run a Debug Adapter Protocol server over stdin and stdout
+    Dap,
+}
+
+#[derive(Args, Debug)]
+struct Run {
+    /// Script to run, or `-` to read it from stdin
+    script: Option<PathBuf>,
+
+    /// Run this code instead of a script; every other argument goes to `ARGS`
+    #[arg(short = 'e', long = "eval", value_name = "CODE")]
+    eval: Option<String>,
+
+    /// Arguments passed to the script as `ARGS`
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    arguments: Vec<String>,
+
+    /// Write the time spent in each Lox function to this file as folded
+    /// stacks, and print a summary to stderr
+    #[arg(long, value_name = "FILE")]
+    profile: Option<PathBuf>,
+
+    /// Count the executions of each line and branch, adding them to the lcov
+    /// report in this file, and print a summary of the run to stderr
+    #[arg(long, value_name = "FILE")]
+    coverage: Option<PathBuf>,
+
+    /// Fold constants and remove dead branches before running. Step counts
+    /// and the lines of some errors then follow the optimized code.
+    #[arg(long, conflicts_with = "coverage")]
+    optimize: bool,
+
+    #[command(flatten)]
+    limits: Limits,
+}
+
+#[derive(Args, Debug)]
+struct Limits {
+    /// Maximum depth of nested function calls before raising `Stack overflow.`
+    #[arg(long, default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+    max_call_depth: usize,
+
+    /// Abort after evaluating this many statements and expressions
+    #[arg(long)]
+    max_steps: Option<u64>,
+
+    /// Abort after running for this many milliseconds
+    #[arg(long, value_name = "MILLISECONDS")]
+    timeout: Option<u64>,
+
+    /// Raise `Out of memory.` once Lox values retain more than this many bytes
+    #[arg(long, value_name = "BYTES")]
+    max_heap: Option<usize>,
+
+    /// Disable the file natives and `import`, for sandboxed runs
+    #[arg(long)]
+    no_filesystem: bool,
+
+    /// Collect reference cycles once closures have captured this many scopes
+    #[arg(long, value_name = "SCOPES", default_value_t = gc::DEFAULT_THRESHOLD)]
+    gc_threshold: usize,
+
+    /// Only collect reference cycles when the script calls `gc()`
+    #[arg(long, conflicts_with = "gc_threshold")]
+    no_gc: bool,
+}
+
+impl Limits {
+    fn interpreter(&self) -> Interpreter {
+        let mut interpreter = Interpreter::new().with_max_call_depth(self.max_call_depth);
+        if let Some(steps) = self.max_steps {
+            interpreter = interpreter.with_step_budget(steps);
+        }
+        if let Some(timeout) = self.timeout {
+            interpreter = interpreter.with_timeout(Duration::from_millis(timeout));
+        }
+        if let Some(bytes) = self.max_heap {
+            interpreter = interpreter.with_max_heap(bytes);
+        }
+        if self.no_filesystem {
+            interpreter = interpreter.without_filesystem();
+        }
+        interpreter = match self.no_gc {
+            true => interpreter.without_gc(),
+            false => interpreter.with_gc_threshold(self.gc_threshold),
+        };
+        interpreter
+    }
+}
+
 fn main() {
-    let mut args = std::env::args();
+    let cli = Cli::parse();
 
-    let (flame_layer, _guard) = FlameLayer::with_file("./tracing.folded").unwrap();
+    let (flame_layer, guard) = match &cli.flamegraph {
+        Some(path) => {
+            let (layer, guard) = FlameLayer::with_file(path).unwrap_or_else(|e| {
+                eprintln!("unable to write {}: {}", path.display(), e);
+                std::process::exit(1);
+            });
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    let trace_layer = cli.trace.as_ref().map(|path| {
+        let file = File::create(path).unwrap_or_else(|e| {
+            eprintln!("unable to write {}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        // Only the interpreter's trace events, without timestamps, so traces
+        // of the same script can be diffed
+        tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .without_time()
+            .with_level(false)
+            .with_target(false)
+            .with_current_span(false)
+            .with_span_list(false)
+            .with_writer(Mutex::new(file))
+            .with_filter(Targets::new().with_target(TRACE_TARGET, Level::TRACE))
+    });
+    if flame_layer.is_some() || trace_layer.is_some() {
+        tracing_subscriber::registry()
+            .with(flame_layer)
+            .with(trace_layer)
+            .init();
+    }
+
+    // Each Lox call nests several Rust frames, so the interpreter runs on a
+    // thread whose stack can hold `max_call_depth` calls.
+    let max_call_depth = match &cli.command {
+        None => cli.run.limits.max_call_depth,
+        Some(Command::Run(run)) => run.limits.max_call_depth,
+        Some(Command::Repl(limits)) => limits.max_call_depth,
+        Some(Command::Debug { limits, .. }) => limits.max_call_depth,
+        Some(Command::Test { limits, .. }) => limits.max_call_depth,
+        Some(_) => DEFAULT_MAX_CALL_DEPTH,
+    };
+    let stack_size = max_call_depth.saturating_mul(STACK_PER_CALL);
+    let result = std::thread::Builder::new()
+        .stack_size(stack_size.max(8 * 1024 * 1024))
+        .spawn(move || match cli.command {
+            None => run_command(cli.run),
+            Some(Command::Run(run)) => run_command(run),
+            Some(Command::Repl(limits)) => repl(limits.interpreter()),
+            Some(Command::Debug {
+                script,
+                arguments,
+                limits,
+            }) => debug(&script, arguments, &limits),
+            Some(Command::Test {
+                paths,
+                filter,
+                limits,
+            }) => test(&paths, filter.as_deref(), &limits),
+            Some(Command::Check { script }) => check(&script),
+            Some(Command::Tokens { script }) => tokens(&script),
+            Some(Command::Ast { script, optimize }) => ast(&script, optimize),
+            Some(Command::Lint {
+                enable,
+                disable,
+                scripts,
+            }) => lint(&scripts, &enable, &disable),
+            Some(Command::Fmt { check, scripts }) => fmt(&scripts, check),
+            Some(Command::Lsp) => lsp::serve(io::stdin().lock(), io::stdout().lock()),
+            Some(Command::Dap) => dap::serve(io::stdin().lock(), io::stdout()),
+        })
+        .expect("unable to spawn interpreter thread")
+        .join()
+        .expect("interpreter thread panicked");
 
-    tracing_subscriber::registry().with(flame_layer).init();
+    let code = match result {
+        Ok(_) => 0,
+        Err(e) => match e.downcast_ref() {
+            Some(&Exit(code)) => code,
+            None => {
+                eprintln!("{:#}", e);
+                1
+            }
+        },
+    };
+
+    // `process::exit` skips destructors, so flush output and the profile first
+    io::stdout().flush().expect("unable to flush stdout");
+    drop(guard);
+    std::process::exit(code);
+}
 
-    let result = match args.len() {
-        1 => repl(),
-        2 => run_file(args.nth(1).unwrap()),
-        _ => {
-            println!("Usage: lox [script]");
-            Ok(())
+fn run_command(run: Run) -> Result<()> {
+    let mut interpreter = run.limits.interpreter();
+    if run.profile.is_some() {
+        interpreter = interpreter.with_profiler();
+    }
+    if run.coverage.is_some() {
+        interpreter = interpreter.with_coverage();
+    }
+    if run.optimize {
+        interpreter = interpreter.with_optimizer();
+    }
+
+    let source = match (run.eval, run.script) {
+        (Some(code), script) => {
+            let arguments = script
+                .map(|script| script.display().to_string())
+                .into_iter()
+                .chain(run.arguments)
+                .collect();
+            interpreter = interpreter.with_arguments(arguments);
+            code
+        }
+        (None, Some(script)) => {
+            interpreter = interpreter.with_arguments(run.arguments);
+            if script != Path::new("-") {
+                interpreter = interpreter.with_script(&script);
+            }
+            read_source(&script)?
         }
+        (None, None) => return repl(interpreter),
     };
 
-    match result {
-        Ok(_) => {}
-        Err(e) => {
-            dbg!(e);
-            std::process::exit(1);
+    let result = run_source(&mut interpreter, &source);
+
+    if let (Some(path), Some(profiler)) = (run.profile, interpreter.profiler()) {
+        let mut file = std::fs::File::create(&path)
+            .with_context(|| format!("unable to write {}", path.display()))?;
+        profiler.write_folded(&mut file)?;
+        profiler.write_summary(&mut io::stderr())?;
+    }
+
+    if let (Some(path), Some(coverage)) = (run.coverage, interpreter.coverage()) {
+        write_coverage(&path, coverage)?;
+    }
+
+    result
+}
+
+// Adds the executions of this run to the report at `path`, so runs of several
+// scripts can share one
+fn write_coverage(path: &Path, coverage: &Coverage) -> Result<()> {
+    // The script didn't parse
+    if coverage.files().next().is_none() {
+        return Ok(());
+    }
+
+    let mut report = match std::fs::read_to_string(path) {
+        Ok(report) => Coverage::read_lcov(&report)
+            .with_context(|| format!("unable to merge with {}", path.display()))?,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Coverage::new(),
+        Err(error) => {
+            return Err(error).with_context(|| format!("unable to read {}", path.display()))
         }
+    };
+    report.merge(coverage.clone());
+
+    let mut file =
+        File::create(path).with_context(|| format!("unable to write {}", path.display()))?;
+    report.write_lcov(&mut file)?;
+    coverage.write_summary(&mut io::stderr())?;
+
+    Ok(())
+}
+
+// Reads a script, where `-` means stdin
+fn read_source(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        return Ok(io::read_to_string(io::stdin())?);
     }
+
+    std::fs::read_to_string(path).with_context(|| format!("unable to read {}", path.display()))
 }
 
-fn run(interpreter: &mut Interpreter, input: &str) -> Result<()> {
-    let mut scanner = crate::scanner::Scanner::new(input.to_string());
+fn parse(source: &str) -> Result<Vec<crate::ast::Stmt>> {
+    let mut scanner = crate::scanner::Scanner::new(source.to_string());
     let tokens = scanner.scan_tokens()?;
 
     let mut parser = crate::parser::Parser::new(tokens);
-    let statements = parser.parse()?;
+    parser.parse()
+}
 
+fn run_source(interpreter: &mut Interpreter, source: &str) -> Result<()> {
+    let statements = parse(source)?;
     interpreter.interpret(statements)?;
 
     Ok(())
 }
 
-fn run_file(filename: String) -> Result<()> {
-    let mut interpreter = Interpreter::new();
-    let file = std::fs::File::open(filename)?;
+fn debug(script: &Path, arguments: Vec<String>, limits: &Limits) -> Result<()> {
+    let source = read_source(script)?;
+    let console = Console::new(&source, io::stdin().lock(), io::stderr());
+    let mut interpreter = limits
+        .interpreter()
+        .with_arguments(arguments)
+        .with_script(script)
+        .with_debugger(console);
+
+    run_source(&mut interpreter, &source)
+}
+
+fn test(paths: &[PathBuf], filter: Option<&str>, limits: &Limits) -> Result<()> {
+    let summary = testing::run(
+        paths,
+        filter,
+        || limits.interpreter(),
+        &mut io::stdout().lock(),
+    )?;
+
+    match summary.ok() {
+        true => Ok(()),
+        false => Err(Exit(1).into()),
+    }
+}
+
+fn check(script: &Path) -> Result<()> {
+    let statements = parse(&read_source(script)?)?;
+
+    let mut resolver = Resolver::new();
+    for statement in statements.iter() {
+        resolver.resolve_stmt(statement)?;
+    }
+
+    Ok(())
+}
+
+fn tokens(script: &Path) -> Result<()> {
+    let tokens = crate::scanner::Scanner::new(read_source(script)?).scan_tokens()?;
+
+    let mut stdout = io::stdout().lock();
+    for token in tokens {
+        writeln!(stdout, "{:>4} {:?}", token.line, token.token_type)?;
+    }
+
+    Ok(())
+}
+
+fn ast(script: &Path, optimize: bool) -> Result<()> {
+    let mut statements = parse(&read_source(script)?)?;
+    if optimize {
+        statements = optimizer::optimize(statements);
+    }
 
-    let program = std::io::read_to_string(file).unwrap();
-    run(&mut interpreter, &program)?;
+    let mut stdout = io::stdout().lock();
+    for statement in statements {
+        writeln!(stdout, "{}", statement)?;
+    }
 
     Ok(())
 }
 
-fn repl() -> Result<()> {
-    let mut interpreter = Interpreter::new();
+fn lint(scripts: &[PathBuf], enable: &[Rule], disable: &[Rule]) -> Result<()> {
+    let mut warned = false;
+
+    for script in scripts {
+        let mut linter = Linter::new();
+        if !enable.is_empty() {
+            linter = linter.with_enabled(enable);
+        }
+        let warnings = linter
+            .with_disabled(disable)
+            .lint(&read_source(script)?)
+            .with_context(|| format!("unable to lint {}", script.display()))?;
+
+        for warning in warnings {
+            println!("{}: {}", script.display(), warning);
+            warned = true;
+        }
+    }
+
+    match warned {
+        true => Err(Exit(1).into()),
+        false => Ok(()),
+    }
+}
+
+fn fmt(scripts: &[PathBuf], check: bool) -> Result<()> {
+    let mut unformatted = false;
+
+    for script in scripts {
+        let source = read_source(script)?;
+        let formatted = formatter::format(&source)
+            .with_context(|| format!("unable to format {}", script.display()))?;
+        if check {
+            if formatted != source {
+                println!("{}", script.display());
+                unformatted = true;
+            }
+        } else if script == Path::new("-") {
+            print!("{}", formatted);
+        } else if formatted != source {
+            std::fs::write(script, formatted)
+                .with_context(|| format!("unable to write {}", script.display()))?;
+        }
+    }
+
+    match check && unformatted {
+        true => Err(Exit(1).into()),
+        false => Ok(()),
+    }
+}
+
+fn repl(mut interpreter: Interpreter) -> Result<()> {
     loop {
         print!("> ");
         std::io::stdout().flush().expect("unable to flush stdout");
@@ -77,9 +552,10 @@ fn repl() -> Result<()> {
         if buf.trim().is_empty() {
             break;
         }
-        match run(&mut interpreter, &buf) {
+        match run_source(&mut interpreter, &buf) {
             Ok(_) => {}
-            Err(e) => println!("{}", e),
+            Err(e) if e.is::<Exit>() => return Err(e),
+            Err(e) => println!("{:#}", e),
         }
         buf.clear();
     }
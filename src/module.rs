@@ -0,0 +1,93 @@
+use crate::environment::Table;
+use crate::error::RuntimeError;
+use crate::heap::{HeapSize, Visited};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::Result;
+
+/// Bookkeeping for `import`: which files are executing and the exports of
+/// every module that has finished, keyed by canonical path.
+#[derive(Debug, Default)]
+pub struct Modules {
+    // Files whose top level is executing, the innermost last, to detect
+    // cycles
+    loading: Vec<PathBuf>,
+    exports: HashMap<PathBuf, Rc<Table>>,
+}
+
+impl HeapSize for Modules {
+    fn heap_size(&self, visited: &mut Visited) -> usize {
+        self.exports
+            .values()
+            .map(|exports| exports.heap_size(visited))
+            .sum()
+    }
+}
+
+impl Modules {
+    pub fn new() -> Self {
+        Modules::default()
+    }
+
+    /// Sets the script that imports start from, so importing it is a cycle.
+    pub fn set_root(&mut self, path: PathBuf) {
+        self.loading = vec![path];
+    }
+
+    /// Resolves `path` relative to the directory of the file containing the
+    /// `import`, or the working directory when not running a file (e.g. the
+    /// REPL).
+    pub fn resolve(importer: Option<&Path>, path: &str) -> Result<PathBuf> {
+        let directory = importer
+            .and_then(|importer| importer.parent())
+            .unwrap_or(Path::new(""));
+
+        std::fs::canonicalize(directory.join(path)).map_err(|error| {
+            RuntimeError::Import(path.to_string(), error.kind().to_string()).into()
+        })
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Rc<Table>> {
+        self.exports.get(path).cloned()
+    }
+
+    pub fn insert(&mut self, path: PathBuf, exports: Table) -> Rc<Table> {
+        let exports = Rc::new(exports);
+        self.exports.insert(path, exports.clone());
+        exports
+    }
+
+    /// Marks `path` as executing, failing if that would import it from itself.
+    pub fn enter(&mut self, path: PathBuf) -> Result<()> {
+        if let Some(start) = self.loading.iter().position(|loading| *loading == path) {
+            let cycle = self.loading[start..]
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|path| self.display(path))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            return Err(RuntimeError::ImportCycle(cycle).into());
+        }
+
+        self.loading.push(path);
+        Ok(())
+    }
+
+    pub fn exit(&mut self) {
+        self.loading.pop();
+    }
+
+    // Paths are shown relative to the script's directory where possible
+    fn display(&self, path: &Path) -> String {
+        let root = self.loading.first().and_then(|root| root.parent());
+
+        match root.and_then(|root| path.strip_prefix(root).ok()) {
+            Some(relative) => relative.display().to_string(),
+            None => path.display().to_string(),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use super::{define, Arguments};
+use crate::data_types::Object;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+
+use anyhow::Result;
+
+/// Defines the assertion natives used by `lox test` as globals.
+pub fn globals(globals: &mut Environment) {
+    define(
+        globals,
+        &[("assert", 2, assert), ("assert_eq", 2, assert_eq)],
+    );
+}
+
+// Raises the message unless the condition is truthy
+fn assert(args: &mut Arguments) -> Result<Object> {
+    match args.get(0).is_truthy() {
+        true => Ok(Object::Nil),
+        false => Err(RuntimeError::AssertionFailed(args.get(1).to_string()).into()),
+    }
+}
+
+fn assert_eq(args: &mut Arguments) -> Result<Object> {
+    let (left, right) = (args.get(0), args.get(1));
+    match left == right {
+        true => Ok(Object::Nil),
+        false => {
+            let message = format!("{} != {}", describe(left), describe(right));
+            Err(RuntimeError::AssertionFailed(message).into())
+        }
+    }
+}
+
+// Strings are quoted so `1` and `"1"` can be told apart
+fn describe(value: &Object) -> String {
+    match value {
+        Object::String(s) => format!("{:?}", s),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data_types::Object;
+    use crate::error::RuntimeError;
+    use crate::natives::eval;
+
+    #[test]
+    fn assertions() {
+        assert_eq!(Object::Nil, eval("assert(1 < 2, \"ordered\")").unwrap());
+        assert_eq!(
+            Object::Nil,
+            eval("assert_eq(\"a\" + \"b\", \"ab\")").unwrap()
+        );
+
+        let err = eval("assert(nil, \"was nil\")").unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::AssertionFailed("was nil".to_string())),
+            err.downcast_ref()
+        );
+
+        let err = eval("assert_eq(1, \"1\")").unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::AssertionFailed("1 != \"1\"".to_string())),
+            err.downcast_ref()
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use super::{define, Arguments};
+use crate::data_types::Object;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+
+use anyhow::Result;
+
+/// Defines the conversion and type introspection natives as globals.
+pub fn globals(globals: &mut Environment) {
+    define(
+        globals,
+        &[
+            ("str", 1, |args| {
+                let s = args.get(0).to_string();
+                args.new_string(s)
+            }),
+            ("num", 1, num),
+            ("bool", 1, |args| {
+                Ok(Object::Boolean(args.get(0).is_truthy()))
+            }),
+            ("type", 1, |args| {
+                let type_name = args.get(0).type_name();
+                args.new_string(type_name)
+            }),
+            ("is_nil", 1, |args| is(args, "nil")),
+            ("is_boolean", 1, |args| is(args, "boolean")),
+            ("is_number", 1, |args| is(args, "number")),
+            ("is_string", 1, |args| is(args, "string")),
+            ("is_function", 1, |args| is(args, "function")),
+            ("is_list", 1, |args| is(args, "list")),
+            ("is_error", 1, |args| is(args, "error")),
+        ],
+    );
+}
+
+fn is(args: &mut Arguments, type_name: &str) -> Result<Object> {
+    Ok(Object::Boolean(args.get(0).type_name() == type_name))
+}
+
+fn num(args: &mut Arguments) -> Result<Object> {
+    match args.get(0) {
+        Object::Number(n) => Ok(Object::Number(*n)),
+        Object::String(s) => parse(s.trim())
+            .map(Object::Number)
+            .ok_or_else(|| RuntimeError::InvalidNumber(s.to_string()).into()),
+        _ => Err(args.error("a number or string")),
+    }
+}
+
+// Only what the scanner reads as a number literal, `digits ('.' digits)?`,
+// optionally negated. `f64::from_str` would also take "inf", "NaN" and "1e5".
+fn parse(s: &str) -> Option<f64> {
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let unsigned = s.strip_prefix('-').unwrap_or(s);
+    let valid = match unsigned.split_once('.') {
+        Some((whole, fraction)) => digits(whole) && digits(fraction),
+        None => digits(unsigned),
+    };
+
+    valid.then(|| s.parse().ok()).flatten()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data_types::Object;
+    use crate::error::RuntimeError;
+    use crate::natives::eval;
+
+    #[test]
+    fn natives() {
+        assert_eq!(Object::Number(42.5), eval("num(\" 42.5 \")").unwrap());
+        assert_eq!(Object::String("42".into()), eval("str(42)").unwrap());
+        assert_eq!(Object::Boolean(false), eval("bool(nil)").unwrap());
+        assert_eq!(
+            Object::String("function".into()),
+            eval("type(clock)").unwrap()
+        );
+        assert_eq!(
+            Object::String("list".into()),
+            eval("type(split(\"\", \"\"))").unwrap()
+        );
+        assert_eq!(Object::Boolean(true), eval("is_string(str(1))").unwrap());
+
+        assert_eq!(Object::Number(-3.0), eval("num(\"-3\")").unwrap());
+
+        for s in ["forty-two", "inf", "NaN", "1e5", ".5", "5.", "+1", ""] {
+            let err = eval(&format!("num(\"{}\")", s)).unwrap_err();
+            assert_eq!(
+                Some(&RuntimeError::InvalidNumber(s.to_string())),
+                err.downcast_ref()
+            );
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::data_types::{Callable, Object, Return};
+use crate::environment::Environment;
+use crate::interpreter::Interpreter;
+
+use std::rc::Rc;
+
+use anyhow::Result;
+
+/// Defines `gc()`, which collects reference cycles and returns the bytes freed.
+pub fn globals(globals: &mut Environment) {
+    globals.define("gc".into(), Object::Function(Rc::new(Collect)));
+}
+
+// Unlike the other natives it needs the interpreter, to record its stats
+#[derive(Debug)]
+struct Collect;
+
+impl Callable for Collect {
+    fn arity(&self) -> u8 {
+        0
+    }
+
+    fn name(&self) -> String {
+        "gc".to_string()
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, _: &[Object]) -> Result<Return> {
+        let collection = interpreter.collect_garbage();
+        Ok(Return::Value(Object::Number(collection.freed as f64)))
+    }
+}
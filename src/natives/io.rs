@@ -0,0 +1,179 @@
+use super::{define, define_filesystem, Arguments};
+use crate::data_types::Object;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+
+/// Defines the file and standard stream natives as globals. The file natives
+/// raise a runtime error when the interpreter disallows filesystem access.
+pub fn globals(globals: &mut Environment) {
+    define_filesystem(
+        globals,
+        &[
+            ("read_file", 1, read_file),
+            ("read_lines", 1, read_lines),
+            ("write_file", 2, |args| write(args, false)),
+            ("append_file", 2, |args| write(args, true)),
+            ("file_exists", 1, |args| {
+                Ok(Object::Boolean(
+                    std::path::Path::new(args.string(0)?).exists(),
+                ))
+            }),
+            ("list_dir", 1, list_dir),
+        ],
+    );
+    define(
+        globals,
+        &[("read_line", 0, read_line), ("eprint", 1, eprint)],
+    );
+}
+
+// Attaches the path to an OS error
+fn io_error(path: &str, error: std::io::Error) -> anyhow::Error {
+    RuntimeError::Io(path.to_string(), error.to_string()).into()
+}
+
+// Accounts for the file's contents before reading them
+fn read(args: &mut Arguments, path: &str) -> Result<String> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| io_error(path, e))?
+        .len();
+    args.allocate(usize::try_from(size).unwrap_or(usize::MAX))?;
+
+    std::fs::read_to_string(path).map_err(|e| io_error(path, e))
+}
+
+fn read_file(args: &mut Arguments) -> Result<Object> {
+    let contents = read(args, args.string(0)?)?;
+
+    Ok(Object::String(contents.into()))
+}
+
+fn read_lines(args: &mut Arguments) -> Result<Object> {
+    let contents = read(args, args.string(0)?)?;
+    let lines = contents
+        .lines()
+        .map(|line| Object::String(line.into()))
+        .collect();
+
+    args.new_list(lines)
+}
+
+fn write(args: &mut Arguments, append: bool) -> Result<Object> {
+    let (path, contents) = (args.string(0)?, args.string(1)?);
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|e| io_error(path, e))?;
+
+    Ok(Object::Nil)
+}
+
+// Names of the directory's entries, sorted
+fn list_dir(args: &mut Arguments) -> Result<Object> {
+    let path = args.string(0)?;
+    let mut names = std::fs::read_dir(path)
+        .and_then(|entries| {
+            entries
+                .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+                .collect::<std::io::Result<Vec<String>>>()
+        })
+        .map_err(|e| io_error(path, e))?;
+    names.sort();
+
+    let names = names
+        .into_iter()
+        .map(|name| args.new_string(name))
+        .collect::<Result<_>>()?;
+    args.new_list(names)
+}
+
+// The next line of stdin without its line ending, or nil at end of input
+fn read_line(args: &mut Arguments) -> Result<Object> {
+    let mut line = String::new();
+    let read = std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| io_error("stdin", e))?;
+
+    if read == 0 {
+        return Ok(Object::Nil);
+    }
+    let trimmed = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(trimmed);
+
+    args.new_string(line)
+}
+
+fn eprint(args: &mut Arguments) -> Result<Object> {
+    eprintln!("{}", args.get(0));
+    Ok(Object::Nil)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data_types::Object;
+    use crate::error::RuntimeError;
+    use crate::interpreter::Interpreter;
+    use crate::natives::eval;
+
+    #[test]
+    fn files() {
+        let directory = std::env::temp_dir().join(format!("lox-io-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("notes.txt").display().to_string();
+
+        let source = format!(
+            "write_file(\"{0}\", \"a\n\") == nil and append_file(\"{0}\", \"b\n\") == nil
+             and join(read_lines(\"{0}\"), \",\") == \"a,b\" and file_exists(\"{0}\")
+             and at(list_dir(\"{1}\"), 0) == \"notes.txt\"",
+            path,
+            directory.display()
+        );
+        assert_eq!(Object::Boolean(true), eval(&source).unwrap());
+
+        let err = eval(&format!(
+            "read_file(\"{}\")",
+            directory.join("missing").display()
+        ))
+        .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(RuntimeError::Io(..))));
+
+        // Files are charged against the heap limit before they're read
+        std::fs::write(directory.join("large.txt"), "x\n".repeat(10_000)).unwrap();
+        for native in ["read_file", "read_lines"] {
+            let source = format!("{}(\"{}\");", native, directory.join("large.txt").display());
+            let tokens = crate::scanner::Scanner::new(source).scan_tokens().unwrap();
+            let err = Interpreter::new()
+                .with_max_heap(10_000)
+                .interpret(crate::parser::Parser::new(tokens).parse().unwrap())
+                .unwrap_err();
+            assert_eq!(Some(&RuntimeError::OutOfMemory), err.downcast_ref());
+        }
+    }
+
+    #[test]
+    fn filesystem_disabled() {
+        let mut interpreter = Interpreter::new().without_filesystem();
+        let tokens = crate::scanner::Scanner::new("file_exists(\".\");".to_string())
+            .scan_tokens()
+            .unwrap();
+
+        let err = interpreter
+            .interpret(crate::parser::Parser::new(tokens).parse().unwrap())
+            .unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::FilesystemDisabled("file_exists".to_string())),
+            err.downcast_ref()
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use super::{namespace, Arguments};
+use crate::data_types::Object;
+use crate::error::RuntimeError;
+
+use anyhow::Result;
+
+/// The `math` namespace.
+pub fn math() -> Object {
+    namespace(
+        "math",
+        &[
+            ("sqrt", 1, |args| unary(args, f64::sqrt)),
+            ("abs", 1, |args| unary(args, f64::abs)),
+            ("floor", 1, |args| unary(args, f64::floor)),
+            ("ceil", 1, |args| unary(args, f64::ceil)),
+            ("round", 1, |args| unary(args, f64::round)),
+            ("sin", 1, |args| unary(args, f64::sin)),
+            ("cos", 1, |args| unary(args, f64::cos)),
+            ("tan", 1, |args| unary(args, f64::tan)),
+            ("log", 1, |args| unary(args, f64::ln)),
+            ("exp", 1, |args| unary(args, f64::exp)),
+            ("pow", 2, |args| binary(args, f64::powf)),
+            ("atan2", 2, |args| binary(args, f64::atan2)),
+            ("min", 2, |args| binary(args, f64::min)),
+            ("max", 2, |args| binary(args, f64::max)),
+            ("mod", 2, |args| integer(args, f64::rem_euclid)),
+            ("idiv", 2, |args| integer(args, f64::div_euclid)),
+        ],
+        &[
+            ("PI", Object::Number(std::f64::consts::PI)),
+            ("E", Object::Number(std::f64::consts::E)),
+            ("INF", Object::Number(f64::INFINITY)),
+            ("NAN", Object::Number(f64::NAN)),
+        ],
+    )
+}
+
+fn unary(args: &mut Arguments, f: fn(f64) -> f64) -> Result<Object> {
+    Ok(Object::Number(f(args.number(0)?)))
+}
+
+fn binary(args: &mut Arguments, f: fn(f64, f64) -> f64) -> Result<Object> {
+    Ok(Object::Number(f(args.number(0)?, args.number(1)?)))
+}
+
+// Euclidean, so `idiv(a, b) * b + mod(a, b) == a` and `mod` is never negative
+fn integer(args: &mut Arguments, f: fn(f64, f64) -> f64) -> Result<Object> {
+    let (a, b) = (args.integer(0)? as f64, args.integer(1)? as f64);
+    if b == 0.0 {
+        return Err(RuntimeError::DivisionByZero.into());
+    }
+
+    Ok(Object::Number(f(a, b)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::math;
+    use crate::data_types::Object;
+    use crate::error::RuntimeError;
+    use crate::interpreter::Interpreter;
+
+    fn call(name: &str, arguments: &[Object]) -> anyhow::Result<Object> {
+        let Object::Namespace(math) = math() else {
+            unreachable!()
+        };
+        let Some(Object::Function(native)) = math.members.get(name) else {
+            panic!("math.{} isn't a function", name)
+        };
+
+        match native.call(&mut Interpreter::new(), arguments)? {
+            crate::data_types::Return::Value(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn natives() {
+        use Object::Number;
+
+        assert_eq!(Number(3.0), call("sqrt", &[Number(9.0)]).unwrap());
+        assert_eq!(
+            Number(8.0),
+            call("pow", &[Number(2.0), Number(3.0)]).unwrap()
+        );
+        assert_eq!(Number(-2.0), call("floor", &[Number(-1.5)]).unwrap());
+        assert_eq!(
+            Number(2.0),
+            call("max", &[Number(1.0), Number(2.0)]).unwrap()
+        );
+        assert_eq!(
+            Number(2.0),
+            call("mod", &[Number(-7.0), Number(3.0)]).unwrap()
+        );
+        assert_eq!(
+            Number(-3.0),
+            call("idiv", &[Number(-7.0), Number(3.0)]).unwrap()
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = call("sqrt", &[Object::String("9".into())]).unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::ArgumentType("sqrt".to_string(), "a number")),
+            err.downcast_ref()
+        );
+
+        let err = call("pow", &[Object::Number(2.0)]).unwrap_err();
+        assert_eq!(Some(&RuntimeError::Arity(2, 1)), err.downcast_ref());
+
+        let err = call("mod", &[Object::Number(1.0), Object::Number(0.0)]).unwrap_err();
+        assert_eq!(Some(&RuntimeError::DivisionByZero), err.downcast_ref());
+
+        for (name, a, b) in [("mod", 7.5, 2.0), ("idiv", 1.5, 0.5)] {
+            let err = call(name, &[Object::Number(a), Object::Number(b)]).unwrap_err();
+            assert_eq!(
+                Some(&RuntimeError::ArgumentType(name.to_string(), "an integer")),
+                err.downcast_ref()
+            );
+        }
+    }
+}
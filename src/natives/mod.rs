@@ -0,0 +1,177 @@
+use crate::data_types::{Callable, Namespace, Object, Return};
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::Result;
+
+pub mod assert;
+pub mod convert;
+pub mod gc;
+pub mod io;
+pub mod math;
+pub mod process;
+pub mod string;
+
+type NativeFn = fn(&mut Arguments) -> Result<Object>;
+
+// Name, arity and implementation of a native
+type Definition = (&'static str, u8, NativeFn);
+
+/// A function implemented in Rust, checked to receive exactly `arity` arguments.
+#[derive(Debug)]
+pub struct Native {
+    name: &'static str,
+    arity: u8,
+    function: NativeFn,
+    // Only callable while the interpreter allows filesystem access
+    filesystem: bool,
+}
+
+impl Callable for Native {
+    fn arity(&self) -> u8 {
+        self.arity
+    }
+
+    fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: &[Object]) -> Result<Return> {
+        if self.filesystem && !interpreter.filesystem() {
+            return Err(RuntimeError::FilesystemDisabled(self.name.to_string()).into());
+        }
+        if arguments.len() != self.arity as usize {
+            return Err(RuntimeError::Arity(self.arity, arguments.len()).into());
+        }
+
+        let mut arguments = Arguments {
+            function: self.name,
+            values: arguments,
+            interpreter,
+        };
+        (self.function)(&mut arguments).map(Return::Value)
+    }
+}
+
+/// The arguments of a native call, unwrapped to the types the native expects.
+/// Borrowed arguments outlive the `Arguments`, so they can be held while
+/// allocating.
+pub struct Arguments<'a> {
+    function: &'static str,
+    values: &'a [Object],
+    // To charge what the native allocates against the heap limit
+    interpreter: &'a mut Interpreter,
+}
+
+impl<'a> Arguments<'a> {
+    fn error(&self, expected: &'static str) -> anyhow::Error {
+        RuntimeError::ArgumentType(self.function.to_string(), expected).into()
+    }
+
+    /// Accounts for `bytes` about to be allocated, see `Interpreter::allocate`.
+    pub fn allocate(&mut self, bytes: usize) -> Result<()> {
+        self.interpreter.allocate(bytes)
+    }
+
+    /// Wraps a string the native built, accounting for its size.
+    pub fn new_string(&mut self, s: impl Into<Rc<str>>) -> Result<Object> {
+        let s: Rc<str> = s.into();
+        self.allocate(s.len())?;
+        Ok(Object::String(s))
+    }
+
+    /// Wraps a list the native built, accounting for its elements but not
+    /// what they hold.
+    pub fn new_list(&mut self, elements: Vec<Object>) -> Result<Object> {
+        self.allocate(
+            std::mem::size_of::<Vec<Object>>() + elements.len() * std::mem::size_of::<Object>(),
+        )?;
+        Ok(Object::List(Rc::new(elements)))
+    }
+
+    pub fn get(&self, index: usize) -> &'a Object {
+        &self.values[index]
+    }
+
+    pub fn number(&self, index: usize) -> Result<f64> {
+        match &self.values[index] {
+            Object::Number(n) => Ok(*n),
+            _ => Err(self.error("a number")),
+        }
+    }
+
+    pub fn integer(&self, index: usize) -> Result<i64> {
+        match &self.values[index] {
+            Object::Number(n) if n.fract() == 0.0 => Ok(*n as i64),
+            _ => Err(self.error("an integer")),
+        }
+    }
+
+    pub fn string(&self, index: usize) -> Result<&'a str> {
+        match &self.values[index] {
+            Object::String(s) => Ok(s),
+            _ => Err(self.error("a string")),
+        }
+    }
+
+    pub fn list(&self, index: usize) -> Result<&'a [Object]> {
+        match &self.values[index] {
+            Object::List(l) => Ok(l),
+            _ => Err(self.error("a list")),
+        }
+    }
+}
+
+fn native(&(name, arity, function): &Definition, filesystem: bool) -> Object {
+    Object::Function(Rc::new(Native {
+        name,
+        arity,
+        function,
+        filesystem,
+    }))
+}
+
+// Defines each native as a global
+fn define(globals: &mut Environment, natives: &[Definition]) {
+    for definition in natives {
+        globals.define(definition.0.into(), native(definition, false));
+    }
+}
+
+// Defines natives that are disabled along with filesystem access
+fn define_filesystem(globals: &mut Environment, natives: &[Definition]) {
+    for definition in natives {
+        globals.define(definition.0.into(), native(definition, true));
+    }
+}
+
+// Builds a namespace object from its natives and constants
+fn namespace(name: &str, natives: &[Definition], constants: &[(&str, Object)]) -> Object {
+    let mut members = HashMap::new();
+
+    for definition in natives {
+        members.insert(definition.0.to_string(), native(definition, false));
+    }
+    for (name, value) in constants {
+        members.insert(name.to_string(), value.clone());
+    }
+
+    Object::Namespace(Rc::new(Namespace {
+        name: name.to_string(),
+        members,
+    }))
+}
+
+// Evaluates a single expression in a fresh interpreter
+#[cfg(test)]
+fn eval(source: &str) -> Result<Object> {
+    let mut interpreter = Interpreter::new();
+    let source = format!("var result = {};", source);
+    let tokens = crate::scanner::Scanner::new(source).scan_tokens()?;
+    interpreter.interpret(crate::parser::Parser::new(tokens).parse()?)?;
+    interpreter.variable("result")
+}
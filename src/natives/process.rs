@@ -0,0 +1,46 @@
+use super::{define, Arguments};
+use crate::data_types::Object;
+use crate::environment::Environment;
+use crate::error::Exit;
+
+use anyhow::Result;
+
+/// Defines the natives for interacting with the process as globals.
+pub fn globals(globals: &mut Environment) {
+    define(globals, &[("getenv", 1, getenv), ("exit", 1, exit)]);
+}
+
+// The variable's value, or nil when it's unset or not valid Unicode
+fn getenv(args: &mut Arguments) -> Result<Object> {
+    match std::env::var(args.string(0)?) {
+        Ok(value) => args.new_string(value),
+        Err(_) => Ok(Object::Nil),
+    }
+}
+
+fn exit(args: &mut Arguments) -> Result<Object> {
+    let code = i32::try_from(args.integer(0)?).map_err(|_| args.error("an exit code"))?;
+
+    Err(Exit(code).into())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data_types::Object;
+    use crate::error::Exit;
+    use crate::natives::eval;
+
+    #[test]
+    fn natives() {
+        std::env::set_var("LOX_TEST_GETENV", "value");
+        assert_eq!(
+            Object::String("value".into()),
+            eval("getenv(\"LOX_TEST_GETENV\")").unwrap()
+        );
+        assert_eq!(Object::Nil, eval("getenv(\"LOX_TEST_UNSET\")").unwrap());
+
+        // `exit` can't be caught, even by a `finally`
+        let err = eval("fun () { try { exit(3); } finally { return 1; } }()").unwrap_err();
+        assert_eq!(Some(&Exit(3)), err.downcast_ref());
+    }
+}
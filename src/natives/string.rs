@@ -0,0 +1,290 @@
+use super::{define, Arguments};
+use crate::data_types::Object;
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+
+use anyhow::Result;
+
+/// Defines the string (and list) natives as globals. Strings are indexed by
+/// Unicode scalar value, and negative indices count back from the end.
+pub fn globals(globals: &mut Environment) {
+    define(
+        globals,
+        &[
+            ("len", 1, len),
+            ("substr", 3, substr),
+            ("slice", 3, slice),
+            ("char_at", 2, char_at),
+            ("at", 2, at),
+            ("index_of", 2, index_of),
+            ("contains", 2, contains),
+            ("starts_with", 2, |args| {
+                Ok(Object::Boolean(
+                    args.string(0)?.starts_with(args.string(1)?),
+                ))
+            }),
+            ("ends_with", 2, |args| {
+                Ok(Object::Boolean(args.string(0)?.ends_with(args.string(1)?)))
+            }),
+            ("upper", 1, |args| {
+                let upper = args.string(0)?.to_uppercase();
+                args.new_string(upper)
+            }),
+            ("lower", 1, |args| {
+                let lower = args.string(0)?.to_lowercase();
+                args.new_string(lower)
+            }),
+            ("trim", 1, |args| {
+                let trimmed = args.string(0)?.trim();
+                args.new_string(trimmed)
+            }),
+            ("replace", 3, replace),
+            ("split", 2, split),
+            ("join", 2, join),
+            ("repeat", 2, repeat),
+            ("ord", 1, ord),
+            ("chr", 1, chr),
+        ],
+    );
+}
+
+// Resolves a possibly negative index into `0..=len`
+fn position(index: i64, len: usize) -> usize {
+    match index {
+        i if i < 0 => len.saturating_sub(i.unsigned_abs() as usize),
+        i => (i as usize).min(len),
+    }
+}
+
+// Resolves a possibly negative index of an element
+fn element(index: i64, len: usize) -> Result<usize> {
+    let resolved = if index < 0 { index + len as i64 } else { index };
+
+    match usize::try_from(resolved) {
+        Ok(i) if i < len => Ok(i),
+        _ => Err(RuntimeError::IndexOutOfRange(index, len).into()),
+    }
+}
+
+fn len(args: &mut Arguments) -> Result<Object> {
+    let len = match args.get(0) {
+        Object::String(s) => s.chars().count(),
+        Object::List(l) => l.len(),
+        _ => return Err(args.error("a string or list")),
+    };
+
+    Ok(Object::Number(len as f64))
+}
+
+fn substr(args: &mut Arguments) -> Result<Object> {
+    let s = args.string(0)?;
+    let start = position(args.integer(1)?, s.chars().count());
+    let length = args.integer(2)?.max(0) as usize;
+
+    args.new_string(s.chars().skip(start).take(length).collect::<String>())
+}
+
+// Elements from `start` up to, but excluding, `end`
+fn slice(args: &mut Arguments) -> Result<Object> {
+    let (start, end) = (args.integer(1)?, args.integer(2)?);
+
+    match args.get(0) {
+        Object::String(s) => {
+            let len = s.chars().count();
+            let (start, end) = (position(start, len), position(end, len));
+            let slice = s.chars().skip(start).take(end.saturating_sub(start));
+            args.new_string(slice.collect::<String>())
+        }
+        Object::List(l) => {
+            let (start, end) = (position(start, l.len()), position(end, l.len()));
+            let slice = l.get(start..end.max(start)).unwrap_or_default();
+            args.new_list(slice.to_vec())
+        }
+        _ => Err(args.error("a string or list")),
+    }
+}
+
+fn char_at(args: &mut Arguments) -> Result<Object> {
+    let s = args.string(0)?;
+    let index = element(args.integer(1)?, s.chars().count())?;
+
+    args.new_string(s.chars().nth(index).unwrap_or_default().to_string())
+}
+
+fn at(args: &mut Arguments) -> Result<Object> {
+    let list = args.list(0)?;
+    let index = element(args.integer(1)?, list.len())?;
+
+    Ok(list[index].clone())
+}
+
+// Index of the first occurrence, or -1
+fn index_of(args: &mut Arguments) -> Result<Object> {
+    let index = match args.get(0) {
+        Object::String(s) => s
+            .find(args.string(1)?)
+            .map(|byte| s[..byte].chars().count()),
+        Object::List(l) => l.iter().position(|value| value == args.get(1)),
+        _ => return Err(args.error("a string or list")),
+    };
+
+    Ok(Object::Number(index.map_or(-1.0, |i| i as f64)))
+}
+
+fn contains(args: &mut Arguments) -> Result<Object> {
+    let contains = match args.get(0) {
+        Object::String(s) => s.contains(args.string(1)?),
+        Object::List(l) => l.contains(args.get(1)),
+        _ => return Err(args.error("a string or list")),
+    };
+
+    Ok(Object::Boolean(contains))
+}
+
+// An empty separator splits the string into its characters
+fn split(args: &mut Arguments) -> Result<Object> {
+    let (s, separator) = (args.string(0)?, args.string(1)?);
+
+    let parts: Vec<Object> = match separator {
+        "" => s
+            .chars()
+            .map(|c| Object::String(c.to_string().into()))
+            .collect(),
+        _ => s
+            .split(separator)
+            .map(|part| Object::String(part.into()))
+            .collect(),
+    };
+
+    // The parts hold the string's text, less the separators
+    args.allocate(s.len())?;
+    args.new_list(parts)
+}
+
+// Accounted for before joining, like `replace` and `repeat`
+fn join(args: &mut Arguments) -> Result<Object> {
+    let (list, separator) = (args.list(0)?, args.string(1)?);
+    let parts: Vec<String> = list.iter().map(Object::to_string).collect();
+    let len = parts.iter().map(String::len).sum::<usize>()
+        + separator.len() * parts.len().saturating_sub(1);
+    args.allocate(len)?;
+
+    Ok(Object::String(parts.join(separator).into()))
+}
+
+// Accounted for before replacing, as the result can be far longer than `s`
+fn replace(args: &mut Arguments) -> Result<Object> {
+    let (s, from, to) = (args.string(0)?, args.string(1)?, args.string(2)?);
+    let matches = s.matches(from).count();
+    args.allocate(
+        (s.len() - matches * from.len()).saturating_add(matches.saturating_mul(to.len())),
+    )?;
+
+    Ok(Object::String(s.replace(from, to).into()))
+}
+
+// Accounted for before repeating, so a huge count fails instead of
+// allocating or overflowing
+fn repeat(args: &mut Arguments) -> Result<Object> {
+    let count =
+        usize::try_from(args.integer(1)?).map_err(|_| args.error("a non-negative count"))?;
+    let s = args.string(0)?;
+    let len = s
+        .len()
+        .checked_mul(count)
+        .filter(|&len| len <= isize::MAX as usize)
+        .ok_or(RuntimeError::OutOfMemory)?;
+    args.allocate(len)?;
+
+    Ok(Object::String(s.repeat(count).into()))
+}
+
+fn ord(args: &mut Arguments) -> Result<Object> {
+    let mut chars = args.string(0)?.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Object::Number(c as u32 as f64)),
+        _ => Err(args.error("a single character")),
+    }
+}
+
+fn chr(args: &mut Arguments) -> Result<Object> {
+    let c = u32::try_from(args.integer(0)?)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| args.error("a Unicode code point"))?;
+
+    args.new_string(c.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data_types::Object;
+    use crate::error::RuntimeError;
+    use crate::interpreter::Interpreter;
+    use crate::natives::eval;
+
+    fn string(s: &str) -> Object {
+        Object::String(s.into())
+    }
+
+    #[test]
+    fn natives() {
+        assert_eq!(Object::Number(5.0), eval("len(\"héllo\")").unwrap());
+        assert_eq!(string("éll"), eval("substr(\"héllo\", 1, 3)").unwrap());
+        assert_eq!(string("llo"), eval("slice(\"héllo\", -3, 10)").unwrap());
+        assert_eq!(string("o"), eval("char_at(\"héllo\", -1)").unwrap());
+        assert_eq!(
+            Object::Number(2.0),
+            eval("index_of(\"héllo\", \"l\")").unwrap()
+        );
+        assert_eq!(
+            Object::Number(-1.0),
+            eval("index_of(\"héllo\", \"z\")").unwrap()
+        );
+        assert_eq!(string("HÉLLO"), eval("upper(\"héllo\")").unwrap());
+        assert_eq!(
+            string("a-b-c"),
+            eval("join(split(\"a b c\", \" \"), \"-\")").unwrap()
+        );
+        assert_eq!(string("b"), eval("at(split(\"abc\", \"\"), 1)").unwrap());
+        assert_eq!(Object::Number(233.0), eval("ord(\"é\")").unwrap());
+        assert_eq!(string("é"), eval("chr(233)").unwrap());
+        assert_eq!(string("abab"), eval("repeat(\"ab\", 2)").unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let err = eval("upper(1)").unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::ArgumentType("upper".to_string(), "a string")),
+            err.downcast_ref()
+        );
+
+        let err = eval("char_at(\"abc\", 3)").unwrap_err();
+        assert_eq!(
+            Some(&RuntimeError::IndexOutOfRange(3, 3)),
+            err.downcast_ref()
+        );
+
+        let err = eval("repeat(\"ab\", 9000000000000000000)").unwrap_err();
+        assert_eq!(Some(&RuntimeError::OutOfMemory), err.downcast_ref());
+    }
+
+    #[test]
+    fn heap_limit() {
+        for source in [
+            "var s = \"x\"; while (true) s = repeat(s, 2);",
+            "var s = \"x\"; while (true) s = replace(s, \"x\", \"xx\");",
+            "var s = \"xy\"; while (true) s = join(split(s, \"\"), s);",
+        ] {
+            let mut interpreter = Interpreter::new().with_max_heap(10_000);
+            let tokens = crate::scanner::Scanner::new(source.to_string())
+                .scan_tokens()
+                .unwrap();
+            let statements = crate::parser::Parser::new(tokens).parse().unwrap();
+            let err = interpreter.interpret(statements).unwrap_err();
+            assert_eq!(Some(&RuntimeError::OutOfMemory), err.downcast_ref());
+        }
+    }
+}
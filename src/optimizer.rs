@@ -0,0 +1,261 @@
+// Constant folding and dead branch elimination, run between parsing and
+// interpretation.
+//
+// Operators over literals are evaluated ahead of time with the interpreter's
+// own semantics. An operation that would fail, like `"a" - 1`, is left in
+// place so it still raises its error at runtime, on its line.
+
+use crate::ast::{Expr, Stmt};
+use crate::interpreter::{binary, unary};
+use crate::token::TokenType;
+
+/// Folds the constant expressions of `statements` and removes the branches
+/// that can never run.
+pub fn optimize(statements: Vec<Stmt>) -> Vec<Stmt> {
+    statements.into_iter().filter_map(statement).collect()
+}
+
+#[allow(clippy::vec_box)]
+fn block(statements: Vec<Box<Stmt>>) -> Vec<Box<Stmt>> {
+    statements
+        .into_iter()
+        .filter_map(|stmt| statement(*stmt).map(Box::new))
+        .collect()
+}
+
+// A statement where one is required, e.g. the body of an `if`
+fn nested(stmt: Stmt) -> Stmt {
+    statement(stmt).unwrap_or(Stmt::Block(Vec::new()))
+}
+
+// The optimized statement, or `None` if it would never do anything
+fn statement(stmt: Stmt) -> Option<Stmt> {
+    let stmt = match stmt {
+        Stmt::Block(body) => Stmt::Block(block(body)),
+        Stmt::If {
+            token,
+            condition,
+            then,
+            els,
+        } => match expression(condition) {
+            Expr::Literal { value, .. } if value.is_truthy() => return statement(*then),
+            Expr::Literal { .. } => return els.and_then(|els| statement(*els)),
+            condition => Stmt::If {
+                token,
+                condition,
+                then: Box::new(nested(*then)),
+                els: els.and_then(|els| statement(*els)).map(Box::new),
+            },
+        },
+        Stmt::While {
+            token,
+            condition,
+            body,
+        } => match expression(condition) {
+            Expr::Literal { value, .. } if !value.is_truthy() => return None,
+            condition => Stmt::While {
+                token,
+                condition,
+                body: Box::new(nested(*body)),
+            },
+        },
+        Stmt::For {
+            token,
+            initializer,
+            condition,
+            increment,
+            body,
+        } => {
+            let initializer = initializer.and_then(|stmt| statement(*stmt));
+            match condition.map(expression) {
+                // Only the initializer runs, in the loop's own scope
+                Some(Expr::Literal { value, .. }) if !value.is_truthy() => {
+                    return initializer.map(|stmt| Stmt::Block(vec![Box::new(stmt)]));
+                }
+                condition => Stmt::For {
+                    token,
+                    initializer: initializer.map(Box::new),
+                    condition,
+                    increment: increment.map(expression),
+                    body: Box::new(nested(*body)),
+                },
+            }
+        }
+        Stmt::Function { name, params, body } => Stmt::Function {
+            name,
+            params,
+            body: block(body),
+        },
+        Stmt::Expression(expr) => Stmt::Expression(Box::new(expression(*expr))),
+        Stmt::Print(expr) => Stmt::Print(Box::new(expression(*expr))),
+        Stmt::Var { name, initializer } => Stmt::Var {
+            name,
+            initializer: initializer.map(expression),
+        },
+        Stmt::Return { token, value } => Stmt::Return {
+            token,
+            value: value.map(expression),
+        },
+        Stmt::Throw { token, value } => Stmt::Throw {
+            token,
+            value: expression(value),
+        },
+        Stmt::Try {
+            body,
+            catch,
+            finally,
+        } => Stmt::Try {
+            body: block(body),
+            catch: catch.map(|(name, handler)| (name, block(handler))),
+            finally: finally.map(block),
+        },
+        stmt @ Stmt::Import { .. } => stmt,
+    };
+
+    Some(stmt)
+}
+
+fn expression(expr: Expr) -> Expr {
+    match expr {
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => match (expression(*left), expression(*right)) {
+            (Expr::Literal { value: l, .. }, Expr::Literal { value: r, .. })
+                if binary(l.clone(), &operator, r.clone()).is_ok() =>
+            {
+                Expr::Literal {
+                    value: binary(l, &operator, r).expect("folded operation"),
+                    line: operator.line,
+                }
+            }
+            (left, right) => Expr::Binary {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+            },
+        },
+        Expr::Unary { operator, right } => match expression(*right) {
+            Expr::Literal { value, .. } if unary(&operator, value.clone()).is_ok() => {
+                Expr::Literal {
+                    value: unary(&operator, value).expect("folded operation"),
+                    line: operator.line,
+                }
+            }
+            right => Expr::Unary {
+                operator,
+                right: Box::new(right),
+            },
+        },
+        Expr::Logical {
+            left,
+            operator,
+            right,
+        } => match expression(*left) {
+            // The operator evaluates to its left operand or its right one
+            Expr::Literal { value, line } => {
+                match (operator.token_type == TokenType::Or, value.is_truthy()) {
+                    (true, true) | (false, false) => Expr::Literal { value, line },
+                    _ => expression(*right),
+                }
+            }
+            left => Expr::Logical {
+                left: Box::new(left),
+                operator,
+                right: Box::new(expression(*right)),
+            },
+        },
+        Expr::Grouping { grouping } => match expression(*grouping) {
+            literal @ Expr::Literal { .. } => literal,
+            grouping => Expr::Grouping {
+                grouping: Box::new(grouping),
+            },
+        },
+        Expr::Assign { name, value, slot } => Expr::Assign {
+            name,
+            value: Box::new(expression(*value)),
+            slot,
+        },
+        Expr::Call {
+            callee,
+            paren,
+            arguments,
+        } => Expr::Call {
+            callee: Box::new(expression(*callee)),
+            paren,
+            arguments: arguments
+                .into_iter()
+                .map(|argument| Box::new(expression(*argument)))
+                .collect(),
+        },
+        Expr::Get { object, name } => Expr::Get {
+            object: Box::new(expression(*object)),
+            name,
+        },
+        Expr::Lambda {
+            token,
+            params,
+            body,
+        } => Expr::Lambda {
+            token,
+            params,
+            body: block(body),
+        },
+        expr @ (Expr::Literal { .. } | Expr::Variable { .. }) => expr,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::optimize;
+    use crate::interpreter::Interpreter;
+
+    fn optimized(source: &str) -> String {
+        let tokens = crate::scanner::Scanner::new(source.to_string())
+            .scan_tokens()
+            .unwrap();
+        let statements = crate::parser::Parser::new(tokens).parse().unwrap();
+        optimize(statements)
+            .iter()
+            .map(|stmt| format!("{}\n", stmt))
+            .collect()
+    }
+
+    #[test]
+    fn folds_constants_and_prunes_branches() {
+        let source = "\
+var day = 60 * 60 * 24;
+var greeting = (\"a\" + \"b\") + \"c\";
+var flag = !(1 < 2) or nil and true;
+if (false) print 1; else print -day;
+while (false) print 2;
+if (1 == 1) print day;
+";
+        let expected = optimized(
+            "\
+var day = 86400;
+var greeting = \"abc\";
+var flag = nil;
+print -day;
+print day;
+",
+        );
+        assert_eq!(expected, optimized(source));
+    }
+
+    #[test]
+    fn keeps_runtime_errors() {
+        assert_eq!("(print (- a 1))\n", optimized("print \"a\" - 1;"));
+
+        let tokens = crate::scanner::Scanner::new("\n\nprint 2 * (\"a\" - 1);".to_string())
+            .scan_tokens()
+            .unwrap();
+        let statements = optimize(crate::parser::Parser::new(tokens).parse().unwrap());
+        let error = Interpreter::new().interpret(statements).unwrap_err();
+        assert_eq!(
+            "[line 3]: cannot perform subtract on non-numeric values",
+            format!("{:#}", error)
+        );
+    }
+}
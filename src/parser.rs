@@ -1,10 +1,13 @@
 use crate::{
     ast::{Expr, Stmt},
     data_types::Object,
-    error::ParseError,
+    error::{ParseError, SyntaxError},
     token::{Token, TokenType, TokenTypeDiscriminants},
 };
 
+use std::collections::HashSet;
+use std::rc::Rc;
+
 use anyhow::{anyhow, Result};
 use tracing::instrument;
 
@@ -12,33 +15,63 @@ use tracing::instrument;
 pub(crate) struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    // String literals seen so far, so equal ones share a string
+    strings: HashSet<Rc<str>>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            strings: HashSet::new(),
+        }
     }
 
     #[instrument(skip(self))]
     pub fn parse(&mut self) -> Result<Vec<Stmt>> {
+        let (statements, errors) = self.parse_recovering()?;
+        match errors.is_empty() {
+            true => Ok(statements),
+            false => Err(ParseError::Syntax(errors).into()),
+        }
+    }
+
+    /// Parses the statements that are valid, alongside errors for the rest.
+    pub fn parse_recovering(&mut self) -> Result<(Vec<Stmt>, Vec<SyntaxError>)> {
         let mut statements = Vec::new();
+        let mut errors = Vec::new();
 
+        // Errors are collected, skipping to the next statement, so every
+        // syntax error in the source is reported at once
         while self.peek().is_some() {
-            // TODO: Capture the error
-            if let Some(d) = self.declaration()? {
-                statements.push(d);
+            match self.declaration() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    let line = match self.current {
+                        0 => self.peek(),
+                        _ => self.prev(),
+                    }
+                    .map_or(0, |t| t.line);
+                    errors.push(SyntaxError {
+                        line,
+                        message: error.to_string(),
+                    });
+                    self.synchronize()?;
+                }
             }
         }
 
-        Ok(statements)
+        Ok((statements, errors))
     }
 
     #[instrument(skip(self))]
-    fn declaration(&mut self) -> Result<Option<Stmt>> {
+    fn declaration(&mut self) -> Result<Stmt> {
         let token = self.peek().ok_or(anyhow!("expected token"))?;
 
-        let statement = match token.token_type {
-            TokenType::Fun => {
+        match token.token_type {
+            // `fun (` starts an anonymous function expression instead
+            TokenType::Fun if !self.peek_next_is(TokenTypeDiscriminants::LeftParen) => {
                 self.next();
                 self.function()
             }
@@ -46,18 +79,12 @@ impl Parser {
                 self.next();
                 self.var_declaration()
             }
-            _ => self.statement(),
-        };
-
-        let statement = match statement {
-            Ok(statement) => Some(statement),
-            Err(_) => {
-                self.synchronize()?;
-                None
+            TokenType::Import => {
+                self.next();
+                self.import_declaration(token)
             }
-        };
-
-        Ok(statement)
+            _ => self.statement(),
+        }
     }
 
     #[instrument(skip(self))]
@@ -69,6 +96,22 @@ impl Parser {
             )))?;
         self.next_if(|t| t == TokenTypeDiscriminants::LeftParen)
             .ok_or(ParseError::ExpectedToken(TokenType::LeftParen))?;
+        let parameters = self.parameters()?;
+
+        self.next_if(|t| t == TokenTypeDiscriminants::LeftBrace)
+            .ok_or(ParseError::ExpectedToken(TokenType::RightBrace))?;
+        let body = self.block()?;
+
+        Ok(Stmt::Function {
+            name,
+            params: parameters,
+            body,
+        })
+    }
+
+    // Parses a parameter list, following its opening `(`
+    #[instrument(skip(self))]
+    fn parameters(&mut self) -> Result<Vec<Token>> {
         let mut parameters = Vec::new();
 
         let token = self.peek().ok_or(anyhow!("expected token"))?;
@@ -97,15 +140,7 @@ impl Parser {
         self.next_if(|t| t == TokenTypeDiscriminants::RightParen)
             .ok_or(ParseError::ExpectedToken(TokenType::RightParen))?;
 
-        self.next_if(|t| t == TokenTypeDiscriminants::LeftBrace)
-            .ok_or(ParseError::ExpectedToken(TokenType::RightBrace))?;
-        let body = self.block()?;
-
-        Ok(Stmt::Function {
-            name,
-            params: parameters,
-            body,
-        })
+        Ok(parameters)
     }
 
     #[instrument(skip(self))]
@@ -132,17 +167,63 @@ impl Parser {
         Ok(Stmt::Var { name, initializer })
     }
 
+    #[instrument(skip(self))]
+    fn import_declaration(&mut self, token: Token) -> Result<Stmt> {
+        let names = match self.next_if(|t| t == TokenTypeDiscriminants::LeftBrace) {
+            Some(_) => {
+                let mut names = Vec::new();
+                loop {
+                    names.push(
+                        self.next_if(|t| t == TokenTypeDiscriminants::Identifier)
+                            .ok_or(ParseError::ExpectedToken(TokenType::Identifier(
+                                "".to_string(),
+                            )))?,
+                    );
+                    if self
+                        .next_if(|t| t == TokenTypeDiscriminants::Comma)
+                        .is_none()
+                    {
+                        break;
+                    }
+                }
+                self.next_if(|t| t == TokenTypeDiscriminants::RightBrace)
+                    .ok_or(ParseError::ExpectedToken(TokenType::RightBrace))?;
+
+                // `from` is only special here, so it isn't a reserved word
+                match self.next().map(|t| t.token_type) {
+                    Some(TokenType::Identifier(from)) if from == "from" => {}
+                    _ => return Err(anyhow!("expected 'from' after import list")),
+                }
+
+                Some(names)
+            }
+            None => None,
+        };
+
+        let path = match self.next().map(|t| t.token_type) {
+            Some(TokenType::String(path)) => path,
+            _ => return Err(ParseError::ExpectedToken(TokenType::String("".into())).into()),
+        };
+
+        self.next_if(|t| t == TokenTypeDiscriminants::Semicolon)
+            .ok_or(ParseError::ExpectedToken(TokenType::Semicolon))?;
+        Ok(Stmt::Import { token, path, names })
+    }
+
     #[instrument(skip(self))]
     fn statement(&mut self) -> Result<Stmt> {
         let token = self.peek().ok_or(anyhow!("expected token"))?;
 
-        self.next_if(|token| match token {
-            TokenTypeDiscriminants::Print
-            | TokenTypeDiscriminants::LeftBrace
-            | TokenTypeDiscriminants::If
-            | TokenTypeDiscriminants::For
-            | TokenTypeDiscriminants::While => true,
-            _ => false,
+        self.next_if(|token| {
+            matches!(
+                token,
+                TokenTypeDiscriminants::Print
+                    | TokenTypeDiscriminants::LeftBrace
+                    | TokenTypeDiscriminants::If
+                    | TokenTypeDiscriminants::For
+                    | TokenTypeDiscriminants::While
+                    | TokenTypeDiscriminants::Try
+            )
         });
 
         let stmt = match token.token_type {
@@ -150,53 +231,47 @@ impl Parser {
                 self.next_if(|t| t == TokenTypeDiscriminants::LeftParen)
                     .ok_or(ParseError::ExpectedToken(TokenType::LeftParen))?;
 
-                let token = self.peek().ok_or(anyhow!("expected token"))?;
-                let initializer: Option<Stmt> = match token.token_type.into() {
+                let next = self.peek().ok_or(anyhow!("expected token"))?;
+                let initializer = match next.token_type.into() {
                     TokenTypeDiscriminants::Semicolon => {
                         self.next();
                         None
                     }
                     TokenTypeDiscriminants::Var => {
                         self.next();
-                        Some(self.var_declaration()?)
+                        Some(Box::new(self.var_declaration()?))
+                    }
+                    _ => {
+                        let initializer = self.expression()?;
+                        self.next_if(|t| t == TokenTypeDiscriminants::Semicolon)
+                            .ok_or(ParseError::ExpectedToken(TokenType::Semicolon))?;
+                        Some(Box::new(Stmt::Expression(Box::new(initializer))))
                     }
-                    _ => Some(Stmt::Expression(Box::new(self.expression()?))),
                 };
 
-                let token = self.peek().ok_or(anyhow!("expected token"))?;
-                let condition: Expr = match token.token_type.into() {
-                    TokenTypeDiscriminants::Semicolon => Expr::Literal(Object::Boolean(true)),
-                    _ => self.expression()?,
+                let next = self.peek().ok_or(anyhow!("expected token"))?;
+                let condition = match next.token_type.into() {
+                    TokenTypeDiscriminants::Semicolon => None,
+                    _ => Some(self.expression()?),
                 };
                 self.next_if(|t| t == TokenTypeDiscriminants::Semicolon)
                     .ok_or(ParseError::ExpectedToken(TokenType::Semicolon))?;
 
-                let token = self.peek().ok_or(anyhow!("expected token"))?;
-                let increment: Option<Expr> = match token.token_type.into() {
+                let next = self.peek().ok_or(anyhow!("expected token"))?;
+                let increment = match next.token_type.into() {
                     TokenTypeDiscriminants::RightParen => None,
                     _ => Some(self.expression()?),
                 };
                 self.next_if(|t| t == TokenTypeDiscriminants::RightParen)
                     .ok_or(ParseError::ExpectedToken(TokenType::RightParen))?;
 
-                let mut body = self.statement()?;
-
-                if let Some(increment) = increment {
-                    body = Stmt::Block(vec![
-                        Box::new(body),
-                        Box::new(Stmt::Expression(Box::new(increment))),
-                    ]);
-                }
-                body = Stmt::While {
+                Stmt::For {
+                    token,
+                    initializer,
                     condition,
-                    body: Box::new(body),
-                };
-
-                if let Some(initializer) = initializer {
-                    body = Stmt::Block(vec![Box::new(initializer), Box::new(body)]);
+                    increment,
+                    body: Box::new(self.statement()?),
                 }
-
-                body
             }
             TokenType::If => {
                 self.next_if(|t| t == TokenTypeDiscriminants::LeftParen)
@@ -216,6 +291,7 @@ impl Parser {
                 };
 
                 Stmt::If {
+                    token,
                     condition,
                     then,
                     els,
@@ -230,6 +306,7 @@ impl Parser {
                 let body = self.statement()?;
 
                 Stmt::While {
+                    token,
                     condition,
                     body: Box::new(body),
                 }
@@ -256,6 +333,58 @@ impl Parser {
 
                 Stmt::Return { token: ret, value }
             }
+            TokenType::Throw => {
+                let token = self
+                    .next_if(|t| t == TokenTypeDiscriminants::Throw)
+                    .ok_or(ParseError::ExpectedToken(TokenType::Throw))?;
+                let value = self.expression()?;
+                self.next_if(|t| t == TokenTypeDiscriminants::Semicolon)
+                    .ok_or(ParseError::ExpectedToken(TokenType::Semicolon))?;
+
+                Stmt::Throw { token, value }
+            }
+            TokenType::Try => {
+                self.next_if(|t| t == TokenTypeDiscriminants::LeftBrace)
+                    .ok_or(ParseError::ExpectedToken(TokenType::LeftBrace))?;
+                let body = self.block()?;
+
+                let catch = match self.next_if(|t| t == TokenTypeDiscriminants::Catch) {
+                    Some(_) => {
+                        self.next_if(|t| t == TokenTypeDiscriminants::LeftParen)
+                            .ok_or(ParseError::ExpectedToken(TokenType::LeftParen))?;
+                        let name = self
+                            .next_if(|t| t == TokenTypeDiscriminants::Identifier)
+                            .ok_or(ParseError::ExpectedToken(TokenType::Identifier(
+                                "".to_string(),
+                            )))?;
+                        self.next_if(|t| t == TokenTypeDiscriminants::RightParen)
+                            .ok_or(ParseError::ExpectedToken(TokenType::RightParen))?;
+                        self.next_if(|t| t == TokenTypeDiscriminants::LeftBrace)
+                            .ok_or(ParseError::ExpectedToken(TokenType::LeftBrace))?;
+                        Some((name, self.block()?))
+                    }
+                    None => None,
+                };
+
+                let finally = match self.next_if(|t| t == TokenTypeDiscriminants::Finally) {
+                    Some(_) => {
+                        self.next_if(|t| t == TokenTypeDiscriminants::LeftBrace)
+                            .ok_or(ParseError::ExpectedToken(TokenType::LeftBrace))?;
+                        Some(self.block()?)
+                    }
+                    None => None,
+                };
+
+                if catch.is_none() && finally.is_none() {
+                    return Err(anyhow!("expected 'catch' or 'finally' after try block"));
+                }
+
+                Stmt::Try {
+                    body,
+                    catch,
+                    finally,
+                }
+            }
             _ => {
                 let value: Expr = self.expression()?;
                 self.next_if(|t| t == TokenTypeDiscriminants::Semicolon)
@@ -267,6 +396,7 @@ impl Parser {
         Ok(stmt)
     }
 
+    #[allow(clippy::vec_box)]
     #[instrument(skip(self))]
     fn block(&mut self) -> Result<Vec<Box<Stmt>>> {
         let mut statements = Vec::new();
@@ -286,11 +416,7 @@ impl Parser {
         self.next_if(|t| t == TokenTypeDiscriminants::RightBrace)
             .ok_or(ParseError::ExpectedToken(TokenType::RightBrace))?;
 
-        let statements = statements
-            .into_iter()
-            .flatten()
-            .map(|s| Box::new(s))
-            .collect();
+        let statements = statements.into_iter().map(Box::new).collect();
 
         Ok(statements)
     }
@@ -308,9 +434,10 @@ impl Parser {
             let value = self.assignment()?;
 
             e = match e {
-                Expr::Variable(v) => Ok(Expr::Assign {
-                    name: v,
+                Expr::Variable { name, slot } => Ok(Expr::Assign {
+                    name,
                     value: Box::new(value),
+                    slot,
                 }),
                 _ => Err(anyhow!("invalid assigment target {}", token)),
             }?;
@@ -373,12 +500,14 @@ impl Parser {
     fn comparison(&mut self) -> Result<Expr> {
         let mut e = self.term()?;
 
-        while let Some(operator) = self.next_if(|t| match t {
-            TokenTypeDiscriminants::Greater
-            | TokenTypeDiscriminants::GreaterEqual
-            | TokenTypeDiscriminants::Less
-            | TokenTypeDiscriminants::LessEqual => true,
-            _ => false,
+        while let Some(operator) = self.next_if(|t| {
+            matches!(
+                t,
+                TokenTypeDiscriminants::Greater
+                    | TokenTypeDiscriminants::GreaterEqual
+                    | TokenTypeDiscriminants::Less
+                    | TokenTypeDiscriminants::LessEqual
+            )
         }) {
             let right = self.term()?;
             e = Expr::Binary {
@@ -445,13 +574,23 @@ impl Parser {
     fn call(&mut self) -> Result<Expr> {
         let mut expr = self.primary()?;
 
-        loop {
-            match self.next_if(|t| t == TokenTypeDiscriminants::LeftParen) {
-                Some(_) => {
-                    expr = self.finish_call(expr)?;
+        while let Some(token) = self
+            .next_if(|t| t == TokenTypeDiscriminants::LeftParen || t == TokenTypeDiscriminants::Dot)
+        {
+            expr = match token.token_type {
+                TokenType::LeftParen => self.finish_call(expr)?,
+                _ => {
+                    let name = self
+                        .next_if(|t| t == TokenTypeDiscriminants::Identifier)
+                        .ok_or(ParseError::ExpectedToken(TokenType::Identifier(
+                            "".to_string(),
+                        )))?;
+                    Expr::Get {
+                        object: Box::new(expr),
+                        name,
+                    }
                 }
-                None => break,
-            }
+            };
         }
 
         Ok(expr)
@@ -491,11 +630,61 @@ impl Parser {
     fn primary(&mut self) -> Result<Expr> {
         if let Some(token) = self.next_if(|_| true) {
             let literal = match token.token_type {
-                TokenType::False => Expr::Literal(Object::Boolean(false)),
-                TokenType::True => Expr::Literal(Object::Boolean(true)),
-                TokenType::Nil => Expr::Literal(Object::Nil),
-                TokenType::Number(n) => Expr::Literal(Object::Number(n)),
-                TokenType::String(s) => Expr::Literal(Object::String(s)),
+                TokenType::False => Expr::Literal {
+                    value: Object::Boolean(false),
+                    line: token.line,
+                },
+                TokenType::True => Expr::Literal {
+                    value: Object::Boolean(true),
+                    line: token.line,
+                },
+                TokenType::Nil => Expr::Literal {
+                    value: Object::Nil,
+                    line: token.line,
+                },
+                TokenType::Number(n) => Expr::Literal {
+                    value: Object::Number(n),
+                    line: token.line,
+                },
+                TokenType::String(s) => Expr::Literal {
+                    value: Object::String(self.intern(s)),
+                    line: token.line,
+                },
+                TokenType::Fun => {
+                    self.next_if(|t| t == TokenTypeDiscriminants::LeftParen)
+                        .ok_or(ParseError::ExpectedToken(TokenType::LeftParen))?;
+                    let params = self.parameters()?;
+                    self.next_if(|t| t == TokenTypeDiscriminants::LeftBrace)
+                        .ok_or(ParseError::ExpectedToken(TokenType::LeftBrace))?;
+                    let body = self.block()?;
+
+                    Expr::Lambda {
+                        token,
+                        params,
+                        body,
+                    }
+                }
+                TokenType::LeftParen if self.is_arrow_function() => {
+                    let params = self.parameters()?;
+                    let arrow = self
+                        .next_if(|t| t == TokenTypeDiscriminants::Arrow)
+                        .ok_or(ParseError::ExpectedToken(TokenType::Arrow))?;
+
+                    // The body is either a block or a single returned expression
+                    let body = match self.next_if(|t| t == TokenTypeDiscriminants::LeftBrace) {
+                        Some(_) => self.block()?,
+                        None => vec![Box::new(Stmt::Return {
+                            token: arrow,
+                            value: Some(self.expression()?),
+                        })],
+                    };
+
+                    Expr::Lambda {
+                        token,
+                        params,
+                        body,
+                    }
+                }
                 TokenType::LeftParen => {
                     let e = self.expression()?;
                     self.next_if(|t| t == TokenTypeDiscriminants::RightParen)
@@ -504,13 +693,29 @@ impl Parser {
                         grouping: Box::new(e),
                     }
                 }
-                TokenType::Identifier(_) => Expr::Variable(token),
-                _ => return Err(anyhow!("dunno {}", token)),
+                TokenType::Identifier(_) => Expr::Variable {
+                    name: token,
+                    slot: None,
+                },
+                _ => return Err(anyhow!("expected expression, found '{}'", token)),
             };
 
             Ok(literal)
         } else {
-            unreachable!()
+            Err(anyhow!("expected expression"))
+        }
+    }
+
+    // The shared copy of a string literal, so equal literals compare by
+    // pointer
+    fn intern(&mut self, s: String) -> Rc<str> {
+        match self.strings.get(s.as_str()) {
+            Some(interned) => interned.clone(),
+            None => {
+                let interned: Rc<str> = s.into();
+                self.strings.insert(interned.clone());
+                interned
+            }
         }
     }
 
@@ -527,6 +732,37 @@ impl Parser {
         }
     }
 
+    fn peek_next_is(&self, token_type: TokenTypeDiscriminants) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|t| TokenTypeDiscriminants::from(&t.token_type) == token_type)
+    }
+
+    // Looks past an opening `(` for `ident, ... ) =>`, telling the parameters
+    // of an arrow function apart from a parenthesized expression.
+    fn is_arrow_function(&self) -> bool {
+        let mut tokens = self.tokens[self.current..]
+            .iter()
+            .map(|t| TokenTypeDiscriminants::from(&t.token_type));
+
+        if tokens.clone().next() != Some(TokenTypeDiscriminants::RightParen) {
+            loop {
+                if tokens.next() != Some(TokenTypeDiscriminants::Identifier) {
+                    return false;
+                }
+                match tokens.next() {
+                    Some(TokenTypeDiscriminants::Comma) => continue,
+                    Some(TokenTypeDiscriminants::RightParen) => break,
+                    _ => return false,
+                }
+            }
+        } else {
+            tokens.next();
+        }
+
+        tokens.next() == Some(TokenTypeDiscriminants::Arrow)
+    }
+
     fn prev(&self) -> Option<Token> {
         self.tokens.get(self.current - 1).cloned()
     }
@@ -542,7 +778,7 @@ impl Parser {
         }
     }
 
-    #[allow(dead_code)] // TODO: Implemented as part of following the book, not used yet
+    // Skips to the likely start of the next statement after a syntax error
     fn synchronize(&mut self) -> Result<()> {
         self.next();
 
@@ -555,17 +791,21 @@ impl Parser {
             match token.token_type {
                 TokenType::Class
                 | TokenType::Fun
+                | TokenType::Import
                 | TokenType::Var
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => return Ok(()),
+                | TokenType::Return
+                | TokenType::Throw
+                | TokenType::Try => return Ok(()),
                 _ => {}
             }
+
+            self.next();
         }
 
-        self.next();
         Ok(())
     }
 }
@@ -582,7 +822,7 @@ mod test {
     fn parse() {
         let tokens = vec![
             Token::new(TokenType::Minus, None, 1),
-            Token::new(TokenType::Number(123 as f64), None, 1),
+            Token::new(TokenType::Number(123_f64), None, 1),
             Token::new(TokenType::Star, None, 1),
             Token::new(TokenType::LeftParen, None, 1),
             Token::new(TokenType::Number(45.67), None, 1),
@@ -596,11 +836,17 @@ mod test {
         let expected = Stmt::Expression(Box::new(Expr::Binary {
             left: Box::new(Expr::Unary {
                 operator: Token::new(TokenType::Minus, None, 1),
-                right: Box::new(Expr::Literal(Object::Number(123 as f64))),
+                right: Box::new(Expr::Literal {
+                    value: Object::Number(123_f64),
+                    line: 1,
+                }),
             }),
             operator: Token::new(TokenType::Star, None, 1),
             right: Box::new(Expr::Grouping {
-                grouping: Box::new(Expr::Literal(Object::Number(45.67))),
+                grouping: Box::new(Expr::Literal {
+                    value: Object::Number(45.67),
+                    line: 1,
+                }),
             }),
         }));
 
@@ -608,4 +854,95 @@ mod test {
 
         assert_eq!(expected, *expr.unwrap().first().unwrap());
     }
+
+    #[test]
+    fn lambda() {
+        let parse = |source: &str| {
+            let tokens = crate::scanner::Scanner::new(source.to_string())
+                .scan_tokens()
+                .unwrap();
+            match Parser::new(tokens).parse().unwrap().remove(0) {
+                Stmt::Expression(expr) => *expr,
+                statement => panic!("expected an expression, got {:?}", statement),
+            }
+        };
+
+        assert!(
+            matches!(parse("fun (a, b) { return a; };"), Expr::Lambda { params, .. } if params.len() == 2)
+        );
+        assert!(
+            matches!(parse("(a, b) => a + b;"), Expr::Lambda { params, body, .. }
+            if params.len() == 2 && matches!(*body[0], Stmt::Return { .. }))
+        );
+        assert!(
+            matches!(parse("() => { print 1; };"), Expr::Lambda { params, .. } if params.is_empty())
+        );
+        assert!(matches!(parse("(a);"), Expr::Grouping { .. }));
+    }
+
+    #[test]
+    fn for_loop() {
+        let parse = |source: &str| {
+            let tokens = crate::scanner::Scanner::new(source.to_string())
+                .scan_tokens()
+                .unwrap();
+            Parser::new(tokens).parse().unwrap().remove(0)
+        };
+
+        // Kept as written rather than desugared into a `while`
+        assert_eq!(
+            "(for (expr (i = 0)) (< i 3) (i = (+ i 1)) (print i))",
+            parse("for (i = 0; i < 3; i = i + 1) print i;").to_string()
+        );
+        assert_eq!(
+            "(for (var i 0) _ _ (block))",
+            parse("for (var i = 0;;) {}").to_string()
+        );
+        assert_eq!(
+            "(for _ _ _ (print 1))",
+            parse("for (;;) print 1;").to_string()
+        );
+    }
+
+    #[test]
+    fn string_literals() {
+        let tokens = crate::scanner::Scanner::new("print \"lox\"; print \"lox\";".to_string())
+            .scan_tokens()
+            .unwrap();
+
+        // Equal literals share one string
+        let literals = Parser::new(tokens)
+            .parse()
+            .unwrap()
+            .into_iter()
+            .map(|statement| match statement {
+                Stmt::Print(expr) => match *expr {
+                    Expr::Literal {
+                        value: Object::String(s),
+                        ..
+                    } => s,
+                    _ => panic!("expected a string literal"),
+                },
+                _ => panic!("expected a print"),
+            })
+            .collect::<Vec<_>>();
+        assert!(Rc::ptr_eq(&literals[0], &literals[1]));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let tokens = crate::scanner::Scanner::new("var a = ;\nprint 1;\nprint (2;".to_string())
+            .scan_tokens()
+            .unwrap();
+
+        let err = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            "[line 1] Error: expected expression, found ';'\n[line 3] Error: expected token )",
+            err.to_string()
+        );
+        match err.downcast_ref() {
+            Some(ParseError::Syntax(errors)) => assert_eq!(3, errors[1].line),
+            _ => panic!("expected syntax errors"),
+        }
+    }
 }
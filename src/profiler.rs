@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+// Name of the frame for code outside of any function
+const SCRIPT: &str = "<script>";
+
+#[derive(Debug)]
+struct Frame {
+    name: String,
+    entered: Instant,
+    // Time spent in the functions this frame called
+    children: Duration,
+}
+
+impl Frame {
+    fn new(name: String) -> Self {
+        Frame {
+            name,
+            entered: Instant::now(),
+            children: Duration::ZERO,
+        }
+    }
+}
+
+/// Time and calls attributed to one Lox function.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    // Time from entering the outermost call to leaving it, so recursion
+    // isn't counted more than once
+    pub total: Duration,
+    // Time spent in the function itself, excluding the functions it called
+    pub own: Duration,
+}
+
+/// Attributes execution time to the Lox functions on the call stack.
+#[derive(Debug)]
+pub struct Profiler {
+    stack: Vec<Frame>,
+    // Own time of each distinct call stack, keyed by its frames joined by `;`
+    stacks: HashMap<String, Duration>,
+    functions: HashMap<String, FunctionProfile>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            stack: vec![Frame::new(SCRIPT.to_string())],
+            stacks: HashMap::new(),
+            functions: HashMap::new(),
+        }
+    }
+
+    pub fn enter(&mut self, name: String) {
+        self.stack.push(Frame::new(name));
+    }
+
+    pub fn exit(&mut self) {
+        if self.stack.len() == 1 {
+            return;
+        }
+        let frame = self.stack.pop().expect("profiled frame");
+        let elapsed = frame.entered.elapsed();
+        let own = elapsed.saturating_sub(frame.children);
+
+        if let Some(caller) = self.stack.last_mut() {
+            caller.children += elapsed;
+        }
+        *self.stacks.entry(self.folded(&frame.name)).or_default() += own;
+
+        let recursive = self.stack.iter().any(|caller| caller.name == frame.name);
+        let function = self.functions.entry(frame.name).or_default();
+        function.calls += 1;
+        function.own += own;
+        if !recursive {
+            function.total += elapsed;
+        }
+    }
+
+    // The current call stack followed by `name`
+    fn folded(&self, name: &str) -> String {
+        let mut folded: Vec<&str> = self.stack.iter().map(|f| f.name.as_str()).collect();
+        folded.push(name);
+        folded.join(";")
+    }
+
+    /// Profiles of every function called so far, by descending own time.
+    pub fn functions(&self) -> Vec<(&str, &FunctionProfile)> {
+        let mut functions: Vec<_> = self
+            .functions
+            .iter()
+            .map(|(name, profile)| (name.as_str(), profile))
+            .collect();
+        functions.sort_by(|a, b| b.1.own.cmp(&a.1.own).then(a.0.cmp(b.0)));
+        functions
+    }
+
+    /// Writes one `frame;frame;... microseconds` line per distinct call stack,
+    /// the folded format read by flamegraph tools.
+    pub fn write_folded(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let script = &self.stack[0];
+        let script_own = script.entered.elapsed().saturating_sub(script.children);
+
+        let mut stacks: Vec<(&str, Duration)> = self
+            .stacks
+            .iter()
+            .map(|(stack, own)| (stack.as_str(), *own))
+            .chain(std::iter::once((SCRIPT, script_own)))
+            .collect();
+        stacks.sort();
+
+        for (stack, own) in stacks {
+            writeln!(out, "{} {}", stack, own.as_micros())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a table of the calls and time of each function.
+    pub fn write_summary(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "{:<24} {:>10} {:>12} {:>12}",
+            "function", "calls", "total ms", "own ms"
+        )?;
+        for (name, profile) in self.functions() {
+            writeln!(
+                out,
+                "{:<24} {:>10} {:>12.3} {:>12.3}",
+                name,
+                profile.calls,
+                profile.total.as_secs_f64() * 1000.0,
+                profile.own.as_secs_f64() * 1000.0
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Profiler;
+
+    #[test]
+    fn folded_stacks() {
+        let mut profiler = Profiler::new();
+        profiler.enter("fib".to_string());
+        profiler.enter("fib".to_string());
+        profiler.exit();
+        profiler.exit();
+        profiler.enter("print".to_string());
+        profiler.exit();
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        let stacks: Vec<&str> = std::str::from_utf8(&folded)
+            .unwrap()
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(
+            vec![
+                "<script>",
+                "<script>;fib",
+                "<script>;fib;fib",
+                "<script>;print"
+            ],
+            stacks
+        );
+
+        let fib = profiler.functions.get("fib").unwrap();
+        assert_eq!(2, fib.calls);
+        assert!(fib.total >= fib.own);
+    }
+}
@@ -1,55 +1,67 @@
-use crate::ast::{Expr, ExpressionVisitor, StatementVisitor, Stmt};
+use crate::ast::{Expr, ExpressionVisitor, Slot, StatementVisitor, Stmt};
 use crate::data_types::Return;
+use crate::error::Line;
 use crate::token::Token;
 
 use std::collections::HashMap;
 
 use anyhow::anyhow;
 
+// Checks scoping rules ahead of execution, e.g. that a local isn't read in
+// its own initializer or declared twice in the same scope. Records which
+// declaration every variable refers to along the way.
 #[derive(Debug)]
-pub struct Resolver /*<'a>*/ {
-    // interpreter: &'a mut Interpreter,
-    locals: Locals,
+pub struct Resolver {
     scopes: Vec<Scope>,
+    // Top-level declarations, which may be referred to before they're declared
+    globals: HashMap<String, Token>,
+    declarations: Vec<Token>,
+    // Variables read or assigned, with the local they resolved to
+    usages: Vec<(Token, Option<Token>)>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Locals {
-    locals: HashMap<usize, Vec<Expr>>,
+#[derive(Debug)]
+struct Local {
+    token: Token,
+    defined: bool,
 }
 
-impl Locals {
-    pub fn new() -> Self {
-        Locals {
-            locals: HashMap::new(),
-        }
-    }
+type Scope = HashMap<String, Local>;
 
-    // pub fn insert(&mut self, depth: usize, expr: Expr) {
-    //     self.locals
-    //         .entry(depth)
-    //         .and_modify(|d| {
-    //             d.push(expr);
-    //         })
-    //         .or_insert(vec![expr]);
-    // }
+/// A variable read or assigned, and the declaration it refers to. Natives and
+/// undeclared globals have none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub usage: Token,
+    pub declaration: Option<Token>,
 }
 
-type Scope = HashMap<String, bool>;
-
 impl Resolver {
-    //<'_> {
-    // pub fn new(interpreter: &'bmut Interpreter) -> Resolver<'b> {
     pub fn new() -> Resolver {
         Resolver {
-            // interpreter,
             scopes: vec![],
-            locals: Locals::new(),
+            globals: HashMap::new(),
+            declarations: Vec::new(),
+            usages: Vec::new(),
         }
     }
 
-    pub fn locals(&self) -> Locals {
-        self.locals.clone()
+    /// Every variable, function, parameter and import declared so far.
+    pub fn declarations(&self) -> &[Token] {
+        &self.declarations
+    }
+
+    pub fn bindings(&self) -> Vec<Binding> {
+        self.usages
+            .iter()
+            .map(|(usage, local)| Binding {
+                usage: usage.clone(),
+                declaration: local.clone().or_else(|| {
+                    let name = usage.lexeme.as_deref()?;
+                    self.globals.get(name).cloned()
+                }),
+            })
+            .collect()
     }
 
     fn begin_scope(&mut self) {
@@ -61,18 +73,31 @@ impl Resolver {
     }
 
     fn declare(&mut self, name: &Token) -> anyhow::Result<()> {
-        let name = name
+        let lexeme = name
             .lexeme
             .clone()
             .ok_or(anyhow!("attempted to declare variable without name"))?;
-        if !self.scopes.is_empty() {
-            if self.scopes.last().unwrap().contains_key(&name) {
-                return Err(anyhow!(
-                    "'{}' has already been declared in this scope",
-                    name
-                ));
+        self.declarations.push(name.clone());
+
+        match self.scopes.last_mut() {
+            Some(scope) if scope.contains_key(&lexeme) => {
+                return Err(
+                    anyhow!("'{}' has already been declared in this scope", lexeme)
+                        .context(Line(name.line)),
+                );
+            }
+            Some(scope) => {
+                scope.insert(
+                    lexeme,
+                    Local {
+                        token: name.clone(),
+                        defined: false,
+                    },
+                );
+            }
+            None => {
+                self.globals.entry(lexeme).or_insert_with(|| name.clone());
             }
-            self.scopes.last_mut().unwrap().insert(name, false); // Safety: scopes is not empty
         }
 
         Ok(())
@@ -81,10 +106,10 @@ impl Resolver {
     fn define(&mut self, name: &Token) -> anyhow::Result<()> {
         let name = name
             .lexeme
-            .clone()
+            .as_ref()
             .ok_or(anyhow!("attempted to define variable without name"))?;
-        if !self.scopes.is_empty() {
-            self.scopes.last_mut().unwrap().insert(name, true); // Safety: scopes is not empty
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(name)) {
+            local.defined = true;
         }
 
         Ok(())
@@ -99,21 +124,18 @@ impl Resolver {
         Ok(())
     }
 
-    fn resolve_local(&mut self, expr: &Expr) -> anyhow::Result<()> {
-        // token: &Token, value: Option<&Expr>)
-        let lexeme = match expr {
-            Expr::Variable(token) => token.lexeme.clone().unwrap(),
-            Expr::Assign { name, value } => name.lexeme.clone().unwrap(),
-            _ => unreachable!(),
-        };
-        // let lexeme = token.lexeme.clone().unwrap();
-        for (depth, scope) in self.scopes.iter().rev().enumerate() {
-            if scope.contains_key(&lexeme) {
-                // self.locals.insert(depth, expr.clone());
-                // self.interpreter.resolve(expr.clone(), depth);
-                return Ok(());
-            }
-        }
+    fn resolve_local(&mut self, name: &Token) -> anyhow::Result<()> {
+        let lexeme = name
+            .lexeme
+            .as_ref()
+            .ok_or(anyhow!("attempted to resolve variable without name"))?;
+        let local = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(lexeme))
+            .map(|local| local.token.clone());
+        self.usages.push((name.clone(), local));
 
         Ok(())
     }
@@ -121,8 +143,8 @@ impl Resolver {
     fn resolve_function(
         &mut self,
         _: &Token,
-        params: &Vec<Token>,
-        body: &Vec<Box<Stmt>>,
+        params: &[Token],
+        body: &[Box<Stmt>],
     ) -> anyhow::Result<()> {
         self.begin_scope();
         for param in params.iter() {
@@ -131,7 +153,7 @@ impl Resolver {
         }
 
         for stmt in body {
-            self.resolve_stmt(&stmt)?;
+            self.resolve_stmt(stmt)?;
         }
 
         self.end_scope();
@@ -140,8 +162,8 @@ impl Resolver {
     }
 }
 
-impl StatementVisitor for Resolver /* TODO <'_> */ {
-    fn visit_block(&mut self, s: &Vec<Box<Stmt>>) -> anyhow::Result<Return> {
+impl StatementVisitor for Resolver {
+    fn visit_block(&mut self, s: &[Box<Stmt>]) -> anyhow::Result<Return> {
         self.begin_scope();
         for stmt in s {
             self.resolve_stmt(stmt)?;
@@ -158,6 +180,7 @@ impl StatementVisitor for Resolver /* TODO <'_> */ {
 
     fn visit_if(
         &mut self,
+        _token: &Token,
         condition: &Expr,
         thn: &Stmt,
         els: Option<&Stmt>,
@@ -175,8 +198,8 @@ impl StatementVisitor for Resolver /* TODO <'_> */ {
     fn visit_function(
         &mut self,
         n: &Token,
-        p: &Vec<Token>,
-        b: &Vec<Box<Stmt>>,
+        p: &[Token],
+        b: &[Box<Stmt>],
     ) -> anyhow::Result<Return> {
         self.declare(n)?;
         self.define(n)?;
@@ -200,6 +223,52 @@ impl StatementVisitor for Resolver /* TODO <'_> */ {
         Ok(Return::None)
     }
 
+    fn visit_throw(&mut self, _: &Token, value: &Expr) -> anyhow::Result<Return> {
+        self.resolve_expr(value)?;
+
+        Ok(Return::None)
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &[Box<Stmt>],
+        catch: Option<(&Token, &[Box<Stmt>])>,
+        finally: Option<&[Box<Stmt>]>,
+    ) -> anyhow::Result<Return> {
+        self.visit_block(body)?;
+
+        if let Some((name, handler)) = catch {
+            self.begin_scope();
+            self.declare(name)?;
+            self.define(name)?;
+            for stmt in handler {
+                self.resolve_stmt(stmt)?;
+            }
+            self.end_scope();
+        }
+
+        if let Some(finally) = finally {
+            self.visit_block(finally)?;
+        }
+
+        Ok(Return::None)
+    }
+
+    // Names bound by a plain `import` are only known once the module has run
+    fn visit_import(
+        &mut self,
+        _token: &Token,
+        _path: &str,
+        names: Option<&[Token]>,
+    ) -> anyhow::Result<Return> {
+        for name in names.unwrap_or_default() {
+            self.declare(name)?;
+            self.define(name)?;
+        }
+
+        Ok(Return::None)
+    }
+
     fn visit_variable(&mut self, n: &Token, i: Option<&Expr>) -> anyhow::Result<Return> {
         self.declare(n)?;
 
@@ -212,21 +281,52 @@ impl StatementVisitor for Resolver /* TODO <'_> */ {
         Ok(Return::None)
     }
 
-    fn visit_while(&mut self, condition: &Expr, body: &Stmt) -> anyhow::Result<Return> {
+    fn visit_while(
+        &mut self,
+        _token: &Token,
+        condition: &Expr,
+        body: &Stmt,
+    ) -> anyhow::Result<Return> {
         self.resolve_expr(condition)?;
         self.resolve_stmt(body)?;
 
         Ok(Return::None)
     }
+
+    fn visit_for(
+        &mut self,
+        _token: &Token,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> anyhow::Result<Return> {
+        self.begin_scope();
+        if let Some(initializer) = initializer {
+            self.resolve_stmt(initializer)?;
+        }
+        if let Some(condition) = condition {
+            self.resolve_expr(condition)?;
+        }
+        if let Some(increment) = increment {
+            self.resolve_expr(increment)?;
+        }
+        self.resolve_stmt(body)?;
+        self.end_scope();
+
+        Ok(Return::None)
+    }
 }
 
-impl ExpressionVisitor<()> for Resolver /*<'_>*/ {
-    fn visit_assignment(&mut self, name: &Token, value: &Expr) -> anyhow::Result<()> {
+impl ExpressionVisitor<()> for Resolver {
+    fn visit_assignment(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _slot: Option<Slot>,
+    ) -> anyhow::Result<()> {
         self.resolve_expr(value)?;
-        self.resolve_local(&Expr::Assign {
-            name: name.clone(),
-            value: Box::new(value.clone()),
-        })?;
+        self.resolve_local(name)?;
 
         Ok(())
     }
@@ -238,12 +338,7 @@ impl ExpressionVisitor<()> for Resolver /*<'_>*/ {
         Ok(())
     }
 
-    fn visit_call(
-        &mut self,
-        callee: &Expr,
-        _: &Token,
-        args: &Vec<Box<Expr>>,
-    ) -> anyhow::Result<()> {
+    fn visit_call(&mut self, callee: &Expr, _: &Token, args: &[Box<Expr>]) -> anyhow::Result<()> {
         self.resolve_expr(callee)?;
 
         for arg in args {
@@ -253,12 +348,22 @@ impl ExpressionVisitor<()> for Resolver /*<'_>*/ {
         Ok(())
     }
 
+    fn visit_get(&mut self, object: &Expr, _: &Token) -> anyhow::Result<()> {
+        self.resolve_expr(object)?;
+
+        Ok(())
+    }
+
     fn visit_grouping(&mut self, grouping: &Expr) -> anyhow::Result<()> {
         self.resolve_expr(grouping)?;
 
         Ok(())
     }
 
+    fn visit_lambda(&mut self, t: &Token, p: &[Token], b: &[Box<Stmt>]) -> anyhow::Result<()> {
+        self.resolve_function(t, p, b)
+    }
+
     fn visit_literal(&mut self, _: &crate::data_types::Object) -> anyhow::Result<()> {
         Ok(())
     }
@@ -276,12 +381,15 @@ impl ExpressionVisitor<()> for Resolver /*<'_>*/ {
         Ok(())
     }
 
-    fn visit_variable(&mut self, n: &Token) -> anyhow::Result<()> {
-        let name = n.lexeme.clone().unwrap();
-        if !self.scopes.is_empty() && self.scopes.last().unwrap().get(&name) == Some(&false) {
-            return Err(anyhow!("can't read local variable in its own initializer"));
+    fn visit_variable(&mut self, n: &Token, _slot: Option<Slot>) -> anyhow::Result<()> {
+        let name = n.lexeme.as_ref().ok_or(anyhow!("variable without name"))?;
+        let local = self.scopes.last().and_then(|scope| scope.get(name));
+        if local.is_some_and(|local| !local.defined) {
+            return Err(
+                anyhow!("can't read local variable in its own initializer").context(Line(n.line))
+            );
         }
 
-        self.resolve_local(&Expr::Variable(n.clone()))
+        self.resolve_local(n)
     }
 }
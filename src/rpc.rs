@@ -0,0 +1,36 @@
+// Messages framed by a `Content-Length` header, the transport shared by the
+// language server and the debug adapter.
+
+use std::io::{BufRead, Write};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// Reads the next message, or `None` at the end of the input.
+pub fn read(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+
+    let mut body = vec![0; length.context("message without a Content-Length")?];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()?;
+    Ok(())
+}
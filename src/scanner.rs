@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
 use crate::error::*;
+use crate::symbol::Symbol;
 use crate::token::{Token, TokenType};
 
 use anyhow::Result;
@@ -9,9 +10,16 @@ use anyhow::Result;
 pub(crate) struct Scanner {
     source: String,
     tokens: Vec<Token>,
+    // Byte offsets into `source` of the token being scanned
     start: usize,
     current: usize,
     line: usize,
+    // Index of the first character of the current line
+    line_start: usize,
+    // Whether comments are kept as tokens rather than skipped
+    comments: bool,
+    // Whether identifiers are interned for the interpreter
+    symbols: bool,
 }
 
 impl Scanner {
@@ -22,9 +30,27 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            comments: false,
+            symbols: true,
         }
     }
 
+    /// Keeps comments as `Comment` tokens, for tools that re-emit the source.
+    /// The parser doesn't accept them.
+    pub(crate) fn with_comments(mut self) -> Self {
+        self.comments = true;
+        self
+    }
+
+    /// Leaves identifiers uninterned, for tools that never run the code.
+    /// Interned names live as long as the process, which a long-running
+    /// language server would only accumulate.
+    pub(crate) fn without_symbols(mut self) -> Self {
+        self.symbols = false;
+        self
+    }
+
     pub(crate) fn scan_tokens(&mut self) -> Result<Vec<Token>> {
         while self.peek().is_some() {
             self.start = self.current;
@@ -60,6 +86,8 @@ impl Scanner {
                 '=' => {
                     if self.next_is('=') {
                         self.add_token(TokenType::EqualEqual, None)
+                    } else if self.next_is('>') {
+                        self.add_token(TokenType::Arrow, None)
                     } else {
                         self.add_token(TokenType::Equal, None)
                     }
@@ -86,16 +114,22 @@ impl Scanner {
                             }
                             let _ = self.next();
                         }
+                        if self.comments {
+                            let text = self.source[self.start + 2..self.current].trim_end();
+                            self.add_token(TokenType::Comment(text.to_string()), None);
+                        }
                     } else {
                         self.add_token(TokenType::Slash, None)
                     }
                 }
                 ' ' | '\r' | '\t' => { /* ignore whitespace */ }
-                '\n' => self.line += 1,
+                '\n' => self.newline(),
                 '"' => self.add_string()?,
                 n if n.is_ascii_digit() => self.add_number()?,
                 i if (i.is_ascii_alphabetic() || i == '_') => self.add_identifier()?,
-                _ => todo!(),
+                c => {
+                    return Err(ParseError::UnexpectedCharacter(c, self.line).into());
+                }
             }
         }
 
@@ -103,13 +137,21 @@ impl Scanner {
     }
 
     fn add_token(&mut self, t: TokenType, lexeme: Option<String>) {
-        self.tokens.push(Token::new(t, lexeme, self.line));
+        self.tokens.push(Token {
+            column: self.start.saturating_sub(self.line_start),
+            ..Token::new(t, lexeme, self.line)
+        });
+    }
+
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
     }
 
     fn add_string(&mut self) -> Result<()> {
         while let Some(c) = self.peek().filter(|c| *c != '"') {
             if c == '\n' {
-                self.line += 1;
+                self.newline();
             }
             let _ = self.next();
         }
@@ -123,9 +165,7 @@ impl Scanner {
         }
 
         let _ = self.next(); // Move past the "
-        let start = self.start + 1;
-        let end = (self.current - 1) - start;
-        let value: String = self.source.chars().skip(start).take(end).collect();
+        let value = self.source[self.start + 1..self.current - 1].to_string();
 
         self.add_token(TokenType::String(value.clone()), Some(value));
         Ok(())
@@ -143,13 +183,7 @@ impl Scanner {
             }
         }
 
-        let value: f64 = self
-            .source
-            .chars()
-            .skip(self.start)
-            .take(self.current - self.start)
-            .collect::<String>()
-            .parse::<f64>()?;
+        let value: f64 = self.source[self.start..self.current].parse()?;
 
         self.add_token(TokenType::Number(value), Some(value.to_string()));
         Ok(())
@@ -164,39 +198,39 @@ impl Scanner {
             let _ = self.next();
         }
 
-        let text: String = self
-            .source
-            .chars()
-            .skip(self.start)
-            .take(self.current - self.start)
-            .collect();
+        let text = self.source[self.start..self.current].to_string();
 
         if let Ok(token_type) = TokenType::from_str(&text) {
             self.add_token(token_type, None);
         } else {
+            let symbol = self.symbols.then(|| Symbol::intern(&text));
             self.add_token(TokenType::Identifier(text.clone()), Some(text));
+            if let Some(token) = self.tokens.last_mut() {
+                token.symbol = symbol;
+            }
         }
 
         Ok(())
     }
 
     fn next(&mut self) -> Option<char> {
-        self.current += 1;
-        self.source.chars().nth(self.current - 1)
+        let c = self.peek()?;
+        self.current += c.len_utf8();
+        Some(c)
     }
 
     fn peek(&self) -> Option<char> {
-        self.source.chars().nth(self.current)
+        self.source[self.current..].chars().next()
     }
 
     fn peek_next(&self) -> Option<char> {
-        self.source.chars().nth(self.current + 1)
+        self.source[self.current..].chars().nth(1)
     }
 
     // TODO: Rename this
     fn next_is(&mut self, c: char) -> bool {
         if self.peek() == Some(c) {
-            self.current += 1;
+            self.current += c.len_utf8();
             true
         } else {
             false
@@ -210,7 +244,7 @@ mod test {
 
     #[test]
     fn test_scanner() {
-        let input = String::from("+-.,({;*})>>===!!==<<=/");
+        let input = String::from("+-.,({;*})>>===!!==<<==>/");
 
         let mut scanner = Scanner::new(input);
         let tokens = scanner.scan_tokens().unwrap();
@@ -234,6 +268,7 @@ mod test {
         assert_eq!(TokenType::Equal, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Less, iter.next().unwrap().token_type);
         assert_eq!(TokenType::LessEqual, iter.next().unwrap().token_type);
+        assert_eq!(TokenType::Arrow, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Slash, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Eof, iter.next().unwrap().token_type);
         assert_eq!(None, iter.next());
@@ -249,6 +284,17 @@ mod test {
 
         assert_eq!(TokenType::Eof, iter.next().unwrap().token_type);
         assert_eq!(None, iter.next());
+
+        let input = String::from("print \"é\"; // one\n//twö");
+        let tokens = Scanner::new(input).with_comments().scan_tokens().unwrap();
+        let comments: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match &token.token_type {
+                TokenType::Comment(text) => Some((text.as_str(), token.line)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![(" one", 1), ("twö", 2)], comments);
     }
 
     #[test]
@@ -299,7 +345,7 @@ mod test {
 
     #[test]
     fn test_numbers() {
-        let inputs = vec![123 as f64, 4567.2301];
+        let inputs = vec![123_f64, 4567.2301];
 
         for input in inputs {
             let mut scanner = Scanner::new(input.to_string());
@@ -315,19 +361,24 @@ mod test {
     #[test]
     fn test_identifiers_and_keywords() {
         let input = r#"and
+                       catch
                        class
                        else
                        false
+                       finally
                        for
                        fun
                        if
+                       import
                        nil
                        or
                        print
                        return
                        super
                        this
+                       throw
                        true
+                       try
                        var
                        while
                        andy
@@ -339,19 +390,24 @@ mod test {
         let mut iter = tokens.iter();
 
         assert_eq!(TokenType::And, iter.next().unwrap().token_type);
+        assert_eq!(TokenType::Catch, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Class, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Else, iter.next().unwrap().token_type);
         assert_eq!(TokenType::False, iter.next().unwrap().token_type);
+        assert_eq!(TokenType::Finally, iter.next().unwrap().token_type);
         assert_eq!(TokenType::For, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Fun, iter.next().unwrap().token_type);
         assert_eq!(TokenType::If, iter.next().unwrap().token_type);
+        assert_eq!(TokenType::Import, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Nil, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Or, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Print, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Return, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Super, iter.next().unwrap().token_type);
         assert_eq!(TokenType::This, iter.next().unwrap().token_type);
+        assert_eq!(TokenType::Throw, iter.next().unwrap().token_type);
         assert_eq!(TokenType::True, iter.next().unwrap().token_type);
+        assert_eq!(TokenType::Try, iter.next().unwrap().token_type);
         assert_eq!(TokenType::Var, iter.next().unwrap().token_type);
         assert_eq!(TokenType::While, iter.next().unwrap().token_type);
         assert_eq!(
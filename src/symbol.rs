@@ -0,0 +1,89 @@
+// Identifiers are interned by the scanner, so variables are compared and
+// hashed as integers instead of strings.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::sync::{LazyLock, Mutex};
+
+// Names are leaked, as there are only as many as distinct identifiers
+static INTERNER: LazyLock<Mutex<HashMap<&'static str, u32>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// An interned name, equal to another only if the names are.
+#[derive(Clone, Copy)]
+pub struct Symbol {
+    id: u32,
+    name: &'static str,
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        let mut interner = INTERNER.lock().expect("interner lock");
+        if let Some((&name, &id)) = interner.get_key_value(name) {
+            return Symbol { id, name };
+        }
+
+        let id = interner.len() as u32;
+        let name: &'static str = Box::leak(name.into());
+        interner.insert(name, id);
+        Symbol { id, name }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+/// A map keyed by symbols, hashing their ids instead of running SipHash.
+pub type SymbolMap<V> = HashMap<Symbol, V, BuildHasherDefault<SymbolHasher>>;
+
+#[derive(Default)]
+pub struct SymbolHasher(u64);
+
+impl Hasher for SymbolHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {
+        unreachable!("symbols hash their id")
+    }
+
+    // Spreads the ids, which count up from 0, over the high bits too, as the
+    // map uses those to pick among buckets
+    fn write_u32(&mut self, id: u32) {
+        self.0 = u64::from(id).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self.name)
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
@@ -0,0 +1,248 @@
+// Runs the `test_*` functions of Lox files for `lox test`, reporting in the
+// style of `cargo test`.
+
+use crate::ast::Stmt;
+use crate::interpreter::Interpreter;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
+
+/// Prefix of the functions run as tests.
+pub const PREFIX: &str = "test_";
+
+/// Counts of the tests a run found.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    pub filtered: usize,
+}
+
+impl Summary {
+    pub fn ok(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+/// Runs the tests in the `.lox` files under `paths`, each in a fresh
+/// interpreter made by `interpreter`. Only tests whose `file::name` contains
+/// `filter` run.
+pub fn run(
+    paths: &[PathBuf],
+    filter: Option<&str>,
+    interpreter: impl Fn() -> Interpreter,
+    out: &mut impl Write,
+) -> Result<Summary> {
+    let mut files = Vec::new();
+    for path in paths {
+        discover(path, &mut files)?;
+    }
+
+    let mut summary = Summary::default();
+    // Names of the failed tests, with their error and output
+    let mut failures = Vec::new();
+
+    for file in files {
+        let source = std::fs::read_to_string(&file)
+            .with_context(|| format!("unable to read {}", file.display()))?;
+        let statements = match parse(&source) {
+            Ok(statements) => statements,
+            Err(error) => {
+                // A file that doesn't parse fails as a whole
+                let id = file.display().to_string();
+                writeln!(out, "test {} ... FAILED", id)?;
+                failures.push((id, format!("{:#}", error), String::new()));
+                summary.failed += 1;
+                continue;
+            }
+        };
+
+        for name in tests(&statements) {
+            let id = format!("{}::{}", file.display(), name);
+            if filter.is_some_and(|filter| !id.contains(filter)) {
+                summary.filtered += 1;
+                continue;
+            }
+
+            let output = Captured::default();
+            let mut interpreter = interpreter().with_script(&file).with_output(output.clone());
+            let result = interpreter
+                .interpret(statements.clone())
+                .and_then(|_| interpreter.interpret(parse(&format!("{}();", name))?));
+            // Its globals and the functions defined in them hold each other
+            drop(interpreter);
+            crate::gc::collect();
+
+            match result {
+                Ok(()) => {
+                    writeln!(out, "test {} ... ok", id)?;
+                    summary.passed += 1;
+                }
+                Err(error) => {
+                    writeln!(out, "test {} ... FAILED", id)?;
+                    failures.push((id, format!("{:#}", error), output.text()));
+                    summary.failed += 1;
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        writeln!(out, "\nfailures:")?;
+        for (id, error, output) in &failures {
+            writeln!(out, "\n---- {} ----\n{}", id, error)?;
+            if !output.is_empty() {
+                write!(out, "output:\n{}", output)?;
+            }
+        }
+    }
+
+    writeln!(
+        out,
+        "\ntest result: {}. {} passed; {} failed; {} filtered out",
+        if summary.ok() { "ok" } else { "FAILED" },
+        summary.passed,
+        summary.failed,
+        summary.filtered
+    )?;
+
+    Ok(summary)
+}
+
+// Adds `path` if it's a file, or the `.lox` files below it in order
+fn discover(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)
+        .with_context(|| format!("unable to read {}", path.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            discover(&entry, files)?;
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension == "lox")
+        {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+fn parse(source: &str) -> Result<Vec<Stmt>> {
+    let tokens = Scanner::new(source.to_string()).scan_tokens()?;
+    Parser::new(tokens).parse()
+}
+
+// Names of the test functions declared at the top level
+fn tests(statements: &[Stmt]) -> Vec<&str> {
+    statements
+        .iter()
+        .filter_map(|statement| match statement {
+            Stmt::Function { name, .. } => name.lexeme.as_deref(),
+            _ => None,
+        })
+        .filter(|name| name.starts_with(PREFIX))
+        .collect()
+}
+
+// Output printed by a test, shown if it fails
+#[derive(Clone, Default)]
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{run, Summary};
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn discovers_filters_and_reports() {
+        let dir = std::env::temp_dir().join(format!("lox-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(
+            dir.join("math.lox"),
+            "fun add(a, b) { return a + b; }
+fun test_add() { assert_eq(add(1, 2), 3); }
+fun test_broken() {
+  print \"adding\";
+  assert_eq(add(2, 2), 5);
+}
+fun helper() { assert(false, \"not a test\"); }
+",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("nested/strings.lox"),
+            "fun test_concat() { assert(\"a\" + \"b\" == \"ab\", \"concat\"); }\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.txt"), "fun test_ignored() {}").unwrap();
+
+        let paths = vec![dir.clone()];
+        let mut out = Vec::new();
+        let summary = run(&paths, None, Interpreter::new, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let math = dir.join("math.lox").display().to_string();
+        let strings = dir.join("nested/strings.lox").display().to_string();
+        let expected = format!(
+            "\
+test {math}::test_add ... ok
+test {math}::test_broken ... FAILED
+test {strings}::test_concat ... ok
+
+failures:
+
+---- {math}::test_broken ----
+[line 5]: Assertion failed: 4 != 5.
+output:
+adding
+
+test result: FAILED. 2 passed; 1 failed; 0 filtered out
+"
+        );
+        assert_eq!(expected, out);
+        assert!(!summary.ok());
+
+        let summary = run(&paths, Some("concat"), Interpreter::new, &mut Vec::new());
+        assert_eq!(
+            Summary {
+                passed: 1,
+                failed: 0,
+                filtered: 2
+            },
+            summary.unwrap()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
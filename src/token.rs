@@ -2,6 +2,7 @@ use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
 use crate::error::ParseError;
+use crate::symbol::Symbol;
 
 use strum_macros::EnumDiscriminants;
 
@@ -10,6 +11,8 @@ pub struct Token {
     pub token_type: TokenType,
     pub lexeme: Option<String>,
     pub line: usize,
+    pub column: usize,          // In characters from the start of the line
+    pub symbol: Option<Symbol>, // Interned name of identifiers, set by the scanner
 }
 
 impl Token {
@@ -18,6 +21,8 @@ impl Token {
             token_type,
             lexeme,
             line,
+            column: 0,
+            symbol: None,
         }
     }
 }
@@ -44,6 +49,7 @@ pub enum TokenType {
     Star,
 
     // One or two character tokens
+    Arrow,
     Bang,
     BangEqual,
     Equal,
@@ -57,22 +63,28 @@ pub enum TokenType {
     Identifier(String),
     String(String),
     Number(f64),
+    Comment(String), // Only kept when scanning for tools, without the `//`
 
     // Keywords
     And,
+    Catch,
     Class,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -101,6 +113,7 @@ impl std::fmt::Display for TokenType {
             Self::Slash => write!(f, "/"),
             Self::Star => write!(f, "*"),
             Self::Bang => write!(f, "!"),
+            Self::Arrow => write!(f, "=>"),
             Self::BangEqual => write!(f, "!="),
             Self::Equal => write!(f, "="),
             Self::EqualEqual => write!(f, "=="),
@@ -111,20 +124,26 @@ impl std::fmt::Display for TokenType {
             Self::Identifier(i) => write!(f, "{}", i),
             Self::String(s) => write!(f, "{}", s),
             Self::Number(n) => write!(f, "{}", n),
+            Self::Comment(c) => write!(f, "//{}", c),
             Self::And => write!(f, "and"),
+            Self::Catch => write!(f, "catch"),
             Self::Class => write!(f, "class"),
             Self::Else => write!(f, "else"),
             Self::Fun => write!(f, "fun"),
             Self::For => write!(f, "for"),
             Self::False => write!(f, "false"),
+            Self::Finally => write!(f, "finally"),
             Self::If => write!(f, "if"),
+            Self::Import => write!(f, "import"),
             Self::Nil => write!(f, "nil"),
             Self::Or => write!(f, "or"),
             Self::Print => write!(f, "print"),
             Self::Return => write!(f, "return"),
             Self::Super => write!(f, "super"),
             Self::This => write!(f, "this"),
+            Self::Throw => write!(f, "throw"),
             Self::True => write!(f, "true"),
+            Self::Try => write!(f, "try"),
             Self::Var => write!(f, "var"),
             Self::While => write!(f, "while"),
             Self::Eof => write!(f, "EOF"),
@@ -148,6 +167,7 @@ impl FromStr for TokenType {
             ";" => Ok(Self::Semicolon),
             "/" => Ok(Self::Slash),
             "*" => Ok(Self::Star),
+            "=>" => Ok(Self::Arrow),
             "!" => Ok(Self::Bang),
             "!=" => Ok(Self::BangEqual),
             "=" => Ok(Self::Equal),
@@ -160,19 +180,24 @@ impl FromStr for TokenType {
             // String(s)=> write!(f, "String({})", s)
             // Number(n)=> write!(f, "Number({})", n)
             "and" => Ok(Self::And),
+            "catch" => Ok(Self::Catch),
             "class" => Ok(Self::Class),
             "else" => Ok(Self::Else),
             "for" => Ok(Self::For),
             "fun" => Ok(Self::Fun),
             "false" => Ok(Self::False),
+            "finally" => Ok(Self::Finally),
             "if" => Ok(Self::If),
+            "import" => Ok(Self::Import),
             "nil" => Ok(Self::Nil),
             "or" => Ok(Self::Or),
             "print" => Ok(Self::Print),
             "return" => Ok(Self::Return),
             "super" => Ok(Self::Super),
             "this" => Ok(Self::This),
+            "throw" => Ok(Self::Throw),
             "true" => Ok(Self::True),
+            "try" => Ok(Self::Try),
             "var" => Ok(Self::Var),
             "while" => Ok(Self::While),
             _ => Err(ParseError::UnknownTokenType),
@@ -0,0 +1,21 @@
+#!/usr/bin/env bash
+# Times each benchmark in cases/benchmark with the release build, skipping
+# the ones that don't run (e.g. those using classes).
+# Usage: test/bench.sh [BENCHMARK...]
+
+set -u
+cd "$(dirname "$0")/.."
+cargo build --release --quiet || exit 1
+
+benchmarks=("$@")
+[ ${#benchmarks[@]} -eq 0 ] && benchmarks=(test/cases/benchmark/*.lox)
+
+TIMEFORMAT=%R
+for benchmark in "${benchmarks[@]}"; do
+	name=$(basename "$benchmark" .lox)
+	if seconds=$( { time target/release/lox "$benchmark" > /dev/null 2>&1; } 2>&1 ); then
+		printf '%-20s %8ss\n' "$name" "$seconds"
+	else
+		printf '%-20s %9s\n' "$name" "-"
+	fi
+done
@@ -146,6 +146,7 @@ unsupported=("to_this"
 while read -r file; do 
 	suite="$(dirname $file)"
 	[ "$suite" = "benchmark" ] && continue  # Skip benchmarks
+	[[ "$suite" = */modules ]] && continue  # Only imported by other cases
 	[ "$suite" = "." ] && suite="basic"
 
 	test_name=${file/\//_} 
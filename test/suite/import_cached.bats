@@ -0,0 +1,8 @@
+# bats file_tags=tag:import
+@test "import/cached.lox" {
+  run target/debug/lox test/cases/import/cached.lox
+
+  [ "${lines[0]}" = "loading counter" ]
+  [ "${lines[1]}" = "1" ]
+  [ "${lines[2]}" = "2" ]
+}
@@ -0,0 +1,7 @@
+# bats file_tags=tag:import
+@test "import/cycle.lox" {
+  run target/debug/lox test/cases/import/cycle.lox
+
+  [ "${lines[0]}" = "start" ]
+  [ "${lines[1]}" = "[line 1]: Import cycle: modules/cycle_a.lox -> modules/cycle_b.lox -> modules/cycle_a.lox." ]
+}
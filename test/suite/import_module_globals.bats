@@ -0,0 +1,8 @@
+# bats file_tags=tag:import
+@test "import/module_globals.lox" {
+  run target/debug/lox test/cases/import/module_globals.lox
+
+  [ "${lines[0]}" = "module" ]
+  [ "${lines[1]}" = "script" ]
+  [ "${lines[2]}" = "[line 7]: undefined variable: 'hidden'" ]
+}
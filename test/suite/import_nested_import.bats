@@ -0,0 +1,8 @@
+# bats file_tags=tag:import
+@test "import/nested_import.lox" {
+  run target/debug/lox test/cases/import/nested_import.lox
+
+  [ "${lines[0]}" = "value" ]
+  [ "${lines[1]}" = "value" ]
+  [ "${lines[2]}" = "[line 11]: undefined variable: 'value'" ]
+}
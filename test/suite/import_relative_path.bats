@@ -0,0 +1,6 @@
+# bats file_tags=tag:import
+@test "import/relative_path.lox" {
+  run target/debug/lox test/cases/import/relative_path.lox
+
+  [ "${lines[0]}" = "value" ]
+}
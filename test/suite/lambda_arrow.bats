@@ -0,0 +1,7 @@
+# bats file_tags=tag:lambda
+@test "lambda/arrow.lox" {
+  run target/debug/lox test/cases/lambda/arrow.lox
+
+  [ "${lines[0]}" = "3" ]
+  [ "${lines[1]}" = "42" ]
+}
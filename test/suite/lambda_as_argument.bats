@@ -0,0 +1,8 @@
+# bats file_tags=tag:lambda
+@test "lambda/as_argument.lox" {
+  run target/debug/lox test/cases/lambda/as_argument.lox
+
+  [ "${lines[0]}" = "42" ]
+  [ "${lines[1]}" = "hi!" ]
+  [ "${lines[2]}" = "now" ]
+}
@@ -0,0 +1,7 @@
+# bats file_tags=tag:lambda
+@test "lambda/block_body.lox" {
+  run target/debug/lox test/cases/lambda/block_body.lox
+
+  [ "${lines[0]}" = "negative" ]
+  [ "${lines[1]}" = "non-negative" ]
+}
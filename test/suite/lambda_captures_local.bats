@@ -0,0 +1,7 @@
+# bats file_tags=tag:lambda
+@test "lambda/captures_local.lox" {
+  run target/debug/lox test/cases/lambda/captures_local.lox
+
+  [ "${lines[0]}" = "42" ]
+  [ "${lines[1]}" = "hello lox" ]
+}
@@ -0,0 +1,10 @@
+# bats file_tags=tag:try
+@test "try/catch_binding.lox" {
+  run target/debug/lox test/cases/try/catch_binding.lox
+
+  [ "${lines[0]}" = "thrown" ]
+  [ "${lines[1]}" = "cannot perform subtract on non-numeric values" ]
+  [ "${lines[2]}" = "9" ]
+  [ "${lines[3]}" = "inner" ]
+  [ "${lines[4]}" = "outer" ]
+}
@@ -0,0 +1,10 @@
+# bats file_tags=tag:try
+@test "try/finally_on_return.lox" {
+  run target/debug/lox test/cases/try/finally_on_return.lox
+
+  [ "${lines[0]}" = "finally" ]
+  [ "${lines[1]}" = "try" ]
+  [ "${lines[2]}" = "left the loop" ]
+  [ "${lines[3]}" = "3" ]
+  [ "${lines[4]}" = "finally" ]
+}
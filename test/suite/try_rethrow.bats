@@ -0,0 +1,7 @@
+# bats file_tags=tag:try
+@test "try/rethrow.lox" {
+  run target/debug/lox test/cases/try/rethrow.lox
+
+  [ "${lines[0]}" = "caught first" ]
+  [ "${lines[1]}" = "outer first again" ]
+}
@@ -0,0 +1,8 @@
+# bats file_tags=tag:try
+@test "try/throw_in_finally.lox" {
+  run target/debug/lox test/cases/try/throw_in_finally.lox
+
+  [ "${lines[0]}" = "replacement" ]
+  [ "${lines[1]}" = "handled inner" ]
+  [ "${lines[2]}" = "pending" ]
+}
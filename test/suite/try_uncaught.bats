@@ -0,0 +1,7 @@
+# bats file_tags=tag:try
+@test "try/uncaught.lox" {
+  run target/debug/lox test/cases/try/uncaught.lox
+
+  [ "${lines[0]}" = "before" ]
+  [ "${lines[1]}" = "[line 2]: boom" ]
+}
@@ -0,0 +1,185 @@
+// Runs the `lox` binary as a shell would, checking its output and exit code.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn lox(arguments: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(arguments)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+// Writes `source` to a script unique to this test
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("lox-cli-{}-{}.lox", std::process::id(), name));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+#[test]
+fn eval() {
+    let output = lox(&["-e", "print ARGS;", "a", "b"], "");
+    assert!(output.status.success());
+    assert_eq!("[a, b]\n", stdout(&output));
+}
+
+#[test]
+fn stdin() {
+    let output = lox(&["-", "x"], "print 1 + 2;\nprint ARGS;");
+    assert!(output.status.success());
+    assert_eq!("3\n[x]\n", stdout(&output));
+}
+
+#[test]
+fn arguments() {
+    let path = script("arguments", "print len(ARGS);\nprint at(ARGS, 1);");
+    let path = path.to_str().unwrap();
+
+    // Arguments after the script go to `ARGS`, even ones that look like flags
+    let output = lox(&[path, "one", "--max-steps"], "");
+    assert_eq!("2\n--max-steps\n", stdout(&output));
+
+    let output = lox(&["run", path, "one", "two"], "");
+    assert_eq!("2\ntwo\n", stdout(&output));
+}
+
+#[test]
+fn exit_codes() {
+    let output = lox(&["-e", "print 1; exit(3); print 2;"], "");
+    assert_eq!(Some(3), output.status.code());
+    assert_eq!("1\n", stdout(&output));
+
+    let output = lox(&["-e", "throw \"boom\";"], "");
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("[line 1]: boom\n", stderr(&output));
+
+    let output = lox(&["-e", "print (1;"], "");
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("[line 1] Error: expected token )\n", stderr(&output));
+
+    let output = lox(&["missing.lox"], "");
+    assert_eq!(Some(1), output.status.code());
+    assert!(stderr(&output).starts_with("unable to read missing.lox"));
+}
+
+#[test]
+fn check() {
+    let path = script("check", "var a = 1;\nprint a + 2;");
+    let output = lox(&["check", path.to_str().unwrap()], "");
+    assert!(output.status.success());
+    assert_eq!("", stdout(&output));
+
+    // Every syntax error is reported, and nothing runs
+    let path = script("check_errors", "var a = ;\nprint 1;\nprint (2;");
+    let output = lox(&["check", path.to_str().unwrap()], "");
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("", stdout(&output));
+    assert_eq!(
+        "[line 1] Error: expected expression, found ';'\n[line 3] Error: expected token )\n",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn check_scopes() {
+    let path = script("check_scopes", "{ var a = 1; var a = 2; }");
+    let output = lox(&["check", path.to_str().unwrap()], "");
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "[line 1]: 'a' has already been declared in this scope\n",
+        stderr(&output)
+    );
+
+    let path = script("check_initializer", "{ var a = a; }");
+    let output = lox(&["check", path.to_str().unwrap()], "");
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(
+        "[line 1]: can't read local variable in its own initializer\n",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn tokens() {
+    let path = script("tokens", "print a;");
+    let output = lox(&["tokens", path.to_str().unwrap()], "");
+    assert!(output.status.success());
+    assert_eq!(
+        "   1 Print\n   1 Identifier(\"a\")\n   1 Semicolon\n   1 Eof\n",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn ast() {
+    let path = script("ast", "var a = 1;\nprint a + 2;");
+    let output = lox(&["ast", path.to_str().unwrap()], "");
+    assert!(output.status.success());
+    assert_eq!("(var a 1)\n(print (+ a 2))\n", stdout(&output));
+}
+
+#[test]
+fn fmt() {
+    let output = lox(&["fmt", "-"], "var  a=1 ;");
+    assert!(output.status.success());
+    assert_eq!("var a = 1;\n", stdout(&output));
+
+    // `--check` lists what needs formatting without touching it
+    let unformatted = script("fmt_unformatted", "print 1+2;");
+    let formatted = script("fmt_formatted", "print 1 + 2;\n");
+    let output = lox(
+        &[
+            "fmt",
+            "--check",
+            unformatted.to_str().unwrap(),
+            formatted.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!(format!("{}\n", unformatted.display()), stdout(&output));
+    assert_eq!("print 1+2;", std::fs::read_to_string(&unformatted).unwrap());
+
+    let output = lox(&["fmt", unformatted.to_str().unwrap()], "");
+    assert!(output.status.success());
+    assert_eq!(
+        "print 1 + 2;\n",
+        std::fs::read_to_string(&unformatted).unwrap()
+    );
+}
+
+#[test]
+fn optimize() {
+    // Code runs as written unless asked, so step counts match the source
+    let source = "var a = 1 + 2 * 3; print a;";
+    let output = lox(&["--max-steps", "7", "-e", source], "");
+    assert_eq!(Some(1), output.status.code());
+
+    let output = lox(&["--optimize", "--max-steps", "7", "-e", source], "");
+    assert!(output.status.success());
+    assert_eq!("7\n", stdout(&output));
+
+    let output = lox(&["--optimize", "--coverage", "lcov.info", "-e", source], "");
+    assert_eq!(Some(2), output.status.code());
+}
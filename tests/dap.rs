@@ -0,0 +1,208 @@
+// Drives `lox dap` as an editor would, over its stdin and stdout.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde_json::{json, Value};
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    seq: u64,
+    // Events read while waiting for a response
+    events: VecDeque<Value>,
+}
+
+impl Client {
+    fn spawn() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+            .arg("dap")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        Client {
+            child,
+            stdin,
+            stdout,
+            seq: 0,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn read(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            assert_ne!(
+                0,
+                self.stdout.read_line(&mut header).unwrap(),
+                "adapter exited"
+            );
+            match header.trim_end() {
+                "" => break,
+                header => {
+                    if let Some(value) = header.strip_prefix("Content-Length: ") {
+                        length = value.parse().unwrap();
+                    }
+                }
+            }
+        }
+
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    // Sends a request and returns the body of its response, which must succeed
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        let response = self.send(command, arguments);
+        assert_eq!(true, response["success"], "{}", response);
+        response["body"].clone()
+    }
+
+    // Sends a request and returns its response
+    fn send(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let body = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+
+        loop {
+            let message = self.read();
+            match message["type"].as_str() {
+                Some("response") => {
+                    assert_eq!(self.seq, message["request_seq"]);
+                    return message;
+                }
+                _ => self.events.push_back(message),
+            }
+        }
+    }
+
+    // Returns the body of the next `event`, skipping other events
+    fn event(&mut self, event: &str) -> Value {
+        loop {
+            let message = self.events.pop_front().unwrap_or_else(|| self.read());
+            if message["event"] == event {
+                return message["body"].clone();
+            }
+        }
+    }
+}
+
+#[test]
+fn breakpoints_stepping_and_inspection() {
+    let program = std::env::temp_dir().join(format!("lox-dap-{}.lox", std::process::id()));
+    std::fs::write(
+        &program,
+        "fun square(n) {
+  var result = n * n;
+  return result;
+}
+var total = 0;
+for (var i = 1; i <= 3; i = i + 1) {
+  total = total + square(i);
+}
+print total;
+",
+    )
+    .unwrap();
+
+    let mut client = Client::spawn();
+    let capabilities = client.request("initialize", json!({"adapterID": "lox"}));
+    assert_eq!(true, capabilities["supportsConditionalBreakpoints"]);
+    client.event("initialized");
+
+    client.request("launch", json!({ "program": program }));
+    let breakpoints = client.request(
+        "setBreakpoints",
+        json!({
+            "source": {"path": program},
+            "breakpoints": [{"line": 3, "condition": "n == 2"}],
+        }),
+    );
+    assert_eq!(true, breakpoints["breakpoints"][0]["verified"]);
+    client.request("configurationDone", json!({}));
+
+    assert_eq!("breakpoint", client.event("stopped")["reason"]);
+    let stack = client.request("stackTrace", json!({"threadId": 1}));
+    let frames: Vec<(&str, u64)> = stack["stackFrames"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|frame| {
+            (
+                frame["name"].as_str().unwrap(),
+                frame["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(vec![("square", 3), ("<script>", 7)], frames);
+
+    let scopes = client.request("scopes", json!({"frameId": 0}));
+    assert_eq!("Locals", scopes["scopes"][0]["name"]);
+    let reference = scopes["scopes"][0]["variablesReference"].clone();
+    let variables = client.request("variables", json!({ "variablesReference": reference }));
+    assert_eq!(
+        json!([
+            {"name": "n", "value": "2", "variablesReference": 0},
+            {"name": "result", "value": "4", "variablesReference": 0},
+        ]),
+        variables["variables"]
+    );
+
+    let evaluated = client.request(
+        "evaluate",
+        json!({"expression": "result + total", "frameId": 0}),
+    );
+    assert_eq!("5", evaluated["result"]);
+
+    client.request("stepOut", json!({"threadId": 1}));
+    assert_eq!("step", client.event("stopped")["reason"]);
+    let stack = client.request("stackTrace", json!({"threadId": 1}));
+    assert_eq!("<script>", stack["stackFrames"][0]["name"]);
+    assert_eq!(7, stack["stackFrames"][0]["line"]);
+
+    client.request("continue", json!({"threadId": 1}));
+    assert_eq!("14\n", client.event("output")["output"]);
+    assert_eq!(0, client.event("exited")["exitCode"]);
+    client.event("terminated");
+
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_file(program).unwrap();
+}
+
+#[test]
+fn launch_without_program() {
+    let program = std::env::temp_dir().join(format!("lox-dap-launch-{}.lox", std::process::id()));
+    std::fs::write(&program, "print 1;\n").unwrap();
+
+    let mut client = Client::spawn();
+    client.request("initialize", json!({"adapterID": "lox"}));
+    client.event("initialized");
+
+    // The failed launch doesn't count, so the adapter waits for another
+    let response = client.send("launch", json!({}));
+    assert_eq!(false, response["success"]);
+    client.request("configurationDone", json!({}));
+    client.request("launch", json!({ "program": program }));
+
+    assert_eq!("1\n", client.event("output")["output"]);
+    assert_eq!(0, client.event("exited")["exitCode"]);
+    client.request("disconnect", json!({}));
+    assert!(client.child.wait().unwrap().success());
+    std::fs::remove_file(program).unwrap();
+}